@@ -0,0 +1,162 @@
+//! Renders a show offline into PNG frames or an animated GIF
+//!
+//!     lumio-render [--seed N] [--fps N] [--layout LAYOUT] [--frames N] [--interval-ms N]
+//!                  [--scale N] [--funnel] (--png DIR | --gif FILE) SHOW
+//!
+//! The show runs for `--frames` frames of simulated time, paced to `--fps`
+//! frames per second, by default `FRAME_RATE` like on the board.  Every
+//! `--interval-ms` of simulated time the current frame is written out.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use fugit::MicrosDurationU64;
+
+use lumio::{conf::FRAME_RATE, frameclock::FrameClock, hardware::Hardware, layout::Layout, programme::Programme, show::{run_frame, Params}};
+use lumio_host::{
+    render::{render, write_png, GifWriter, Projection},
+    simulator::{parse_layout, Simulator, FIRMWARE_SEED},
+};
+
+const USAGE: &str = "usage: lumio-render [--seed N] [--fps N] [--layout LAYOUT] [--frames N] [--interval-ms N]
+                    [--scale N] [--funnel] (--png DIR | --gif FILE) SHOW";
+
+enum Output {
+    Png(PathBuf),
+    Gif(PathBuf),
+}
+
+struct Args {
+    seed: u64,
+    fps: u32,
+    layout: Layout,
+    frames: u64,
+    interval_ms: u64,
+    scale: usize,
+    projection: Projection,
+    output: Option<Output>,
+    show: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        seed: FIRMWARE_SEED,
+        fps: FRAME_RATE,
+        layout: Layout::FLOWER,
+        frames: 1000,
+        interval_ms: 40,
+        scale: 8,
+        projection: Projection::Unrolled,
+        output: None,
+        show: None,
+    };
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--seed" => args.seed = parse_number(&arg, argv.next())?,
+            "--fps" => args.fps = parse_number(&arg, argv.next())?.clamp(1, 1000) as u32,
+            "--layout" => args.layout = parse_layout(&argv.next().unwrap_or_default())?,
+            "--frames" => args.frames = parse_number(&arg, argv.next())?,
+            "--interval-ms" => args.interval_ms = parse_number(&arg, argv.next())?.max(1),
+            "--scale" => args.scale = parse_number(&arg, argv.next())?.max(1) as usize,
+            "--funnel" => args.projection = Projection::Funnel,
+            "--png" => args.output = Some(Output::Png(parse_path(&arg, argv.next())?)),
+            "--gif" => args.output = Some(Output::Gif(parse_path(&arg, argv.next())?)),
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ => args.show = Some(arg),
+        }
+    }
+    Ok(args)
+}
+
+fn parse_number(option: &str, value: Option<String>) -> Result<u64, String> {
+    value
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| format!("{option} needs a number"))
+}
+
+fn parse_path(option: &str, value: Option<String>) -> Result<PathBuf, String> {
+    value.map(PathBuf::from).ok_or_else(|| format!("{option} needs a path"))
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("{message}");
+            }
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let (Some(output), Some(name)) = (args.output.as_ref(), args.show.as_ref()) else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    match run(&args, output, name) {
+        Ok(written) => {
+            eprintln!("{name}: {} frames simulated, {written} written", args.frames);
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args, output: &Output, name: &str) -> Result<usize, String> {
+    let mut programme = Programme::new();
+    let mut shows = programme.shows::<Simulator>();
+    let Some(show) = shows.iter_mut().find(|show| show.name() == name) else {
+        return Err(format!("no show named {name}, see lumio-sim --list"));
+    };
+
+    let mut sim = Simulator::with_layout(args.seed, args.layout);
+
+    let interval = MicrosDurationU64::millis(args.interval_ms);
+    let mut next_capture = sim.get_time();
+    // GIF delays are centiseconds, browsers do not go below two of them.
+    let gif_delay = (args.interval_ms / 10).max(2) as u16;
+    let mut written = 0;
+
+    let mut gif = None;
+    if let Output::Png(dir) = output {
+        std::fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+    }
+
+    show.init(&mut sim, Params::new());
+    let mut clock = FrameClock::new(args.fps);
+    for _ in 0..args.frames {
+        run_frame(*show, &mut sim, &mut clock);
+
+        if sim.get_time() < next_capture {
+            continue;
+        }
+        next_capture += interval;
+
+        let canvas = render(sim.led_strip(), args.projection, args.scale);
+        match output {
+            Output::Png(dir) => {
+                let path = dir.join(format!("frame_{written:05}.png"));
+                write_png(&path, &canvas).map_err(|err| format!("{}: {err}", path.display()))?;
+            }
+            Output::Gif(path) => {
+                if gif.is_none() {
+                    gif = Some(GifWriter::create(path, canvas.width, canvas.height)
+                        .map_err(|err| format!("{}: {err}", path.display()))?);
+                }
+                if let Some(gif) = gif.as_mut() {
+                    gif.add_frame(&canvas, gif_delay)
+                        .map_err(|err| format!("{}: {err}", path.display()))?;
+                }
+            }
+        }
+        written += 1;
+    }
+    show.teardown(&mut sim);
+
+    Ok(written)
+}
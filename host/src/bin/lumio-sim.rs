@@ -0,0 +1,104 @@
+//! Runs the shows in a terminal
+//!
+//!     lumio-sim [--seed N] [--layout LAYOUT] [SHOW]
+//!     lumio-sim --list
+//!
+//! Without a show name the playlist runs like on the board.  The
+//! layout is `flower` (default), `half-flower` or e.g. `12x60-open`.
+
+use std::process::ExitCode;
+
+use lumio::{
+    conf::PLAYLIST,
+    layout::Layout,
+    playlist::Entry,
+    programme::Programme,
+    show::{run_next, run_show},
+};
+use lumio_host::{simulator::{parse_layout, Simulator, FIRMWARE_SEED}, terminal::{Terminal, KEY_HELP}};
+
+struct Args {
+    seed: u64,
+    layout: Layout,
+    show: Option<String>,
+    list: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args { seed: FIRMWARE_SEED, layout: Layout::FLOWER, show: None, list: false };
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--seed" => args.seed = parse_number(&arg, argv.next())?,
+            "--layout" => args.layout = parse_layout(&argv.next().unwrap_or_default())?,
+            "--list" => args.list = true,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ => args.show = Some(arg),
+        }
+    }
+    Ok(args)
+}
+
+fn parse_number(option: &str, value: Option<String>) -> Result<u64, String> {
+    value
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| format!("{option} needs a number"))
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("{message}");
+            }
+            eprintln!("usage: lumio-sim [--seed N] [--layout LAYOUT] [SHOW]\n       lumio-sim --list\n\n{KEY_HELP}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut programme = Programme::new();
+    let mut shows = programme.shows::<Terminal>();
+
+    if args.list {
+        for show in shows.iter() {
+            println!("{}", show.name());
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let selected = match &args.show {
+        Some(name) => match shows.iter().position(|show| show.name() == name) {
+            Some(index) => Some(index),
+            None => {
+                eprintln!("no show named {name}, see lumio-sim --list");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    let sim = Simulator::with_layout(args.seed, args.layout);
+
+    let mut terminal = match Terminal::new(sim) {
+        Ok(terminal) => terminal,
+        Err(err) => {
+            eprintln!("cannot set up the terminal: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut playlist = PLAYLIST;
+    while !terminal.quit_requested() {
+        match selected {
+            Some(index) => {
+                let entry = Entry::new(shows[index].name());
+                run_show(&mut shows, index, &mut terminal, entry);
+            }
+            None => run_next(&mut playlist, &mut shows, &mut terminal),
+        }
+    }
+
+    ExitCode::SUCCESS
+}
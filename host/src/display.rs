@@ -0,0 +1,15 @@
+use lumio::{apa102::MAX_GLOBAL, led::Color, ledstrip::LEDStrip};
+
+const DISPLAY_GAMMA: f32 = 1.0 / 2.2;
+
+/// The colour as it is sent to the strip.  The LEDs are driven linearly while
+/// screens expect sRGB, so dark LED values are lifted to look roughly like
+/// they do on the strips.
+pub fn display_rgb(led_strip: &LEDStrip, color: Color) -> [u8; 3] {
+    let (global, channels) = led_strip.encode(color);
+    channels.map(|channel| display_channel(channel as f32 * global as f32 / (255.0 * MAX_GLOBAL as f32)))
+}
+
+fn display_channel(intensity: f32) -> u8 {
+    (intensity.powf(DISPLAY_GAMMA) * 255.0).round() as u8
+}
@@ -0,0 +1,12 @@
+//! Host side of the LED strip flower
+//!
+//! Runs the shows of the `lumio` crate on a desktop machine: `simulator`
+//! implements `lumio::hardware::Hardware` without any hardware, `terminal`
+//! draws the simulated strips into a terminal and `render` into images.
+//! `upload` encodes frames for the compressed upload to the flower.
+
+pub mod display;
+pub mod render;
+pub mod simulator;
+pub mod terminal;
+pub mod upload;
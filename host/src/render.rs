@@ -0,0 +1,129 @@
+use std::{f32::consts::PI, fs::File, io::{self, BufWriter}, path::Path};
+
+use lumio::ledstrip::LEDStrip;
+
+use crate::display::display_rgb;
+
+/// Radius of the disk the strips are mounted on, in LED pitches.
+const FUNNEL_INNER_RADIUS: f32 = 8.0;
+/// Fraction of a LED pitch that is lit in the funnel view.
+const FUNNEL_DOT_SIZE: f32 = 0.7;
+
+/// An RGB image of one frame.
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas { width, height, data: vec![0; width * height * 3] }
+    }
+
+    fn set(&mut self, x: usize, y: usize, rgb: [u8; 3]) {
+        let offset = (y * self.width + x) * 3;
+        self.data[offset..offset + 3].copy_from_slice(&rgb);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Projection {
+    /// The cylinder cut open: strips as columns, LED 0 at the bottom.
+    Unrolled,
+    /// The funnel seen from above: strips as spokes, LED 0 at the inside.
+    Funnel,
+}
+
+pub fn render(led_strip: &LEDStrip, projection: Projection, scale: usize) -> Canvas {
+    match projection {
+        Projection::Unrolled => unrolled(led_strip, scale),
+        Projection::Funnel => funnel(led_strip, scale),
+    }
+}
+
+fn led_rgb(led_strip: &LEDStrip, strip: usize, y: usize) -> [u8; 3] {
+    let layout = led_strip.layout();
+    if !layout.has_led(strip, y) {
+        return [0; 3];
+    }
+    display_rgb(led_strip, led_strip.output(layout.index(strip, y)))
+}
+
+fn unrolled(led_strip: &LEDStrip, scale: usize) -> Canvas {
+    let layout = led_strip.layout();
+    let mut canvas = Canvas::new(layout.strips() * scale, layout.length() * scale);
+    for strip in 0..layout.strips() {
+        for y in 0..layout.length() {
+            let rgb = led_rgb(led_strip, strip, y);
+            let top = (layout.length() - 1 - y) * scale;
+            for py in top..top + scale {
+                for px in strip * scale..(strip + 1) * scale {
+                    canvas.set(px, py, rgb);
+                }
+            }
+        }
+    }
+    canvas
+}
+
+fn funnel(led_strip: &LEDStrip, scale: usize) -> Canvas {
+    let layout = led_strip.layout();
+    let (strips, length) = (layout.strips(), layout.length());
+    let outer_radius = FUNNEL_INNER_RADIUS + length as f32;
+    let size = (2.0 * outer_radius * scale as f32).ceil() as usize + 1;
+    let center = size as f32 / 2.0;
+    let mut canvas = Canvas::new(size, size);
+
+    for py in 0..size {
+        for px in 0..size {
+            let dx = (px as f32 + 0.5 - center) / scale as f32;
+            let dy = (center - py as f32 - 0.5) / scale as f32;
+            let radius = (dx * dx + dy * dy).sqrt() - FUNNEL_INNER_RADIUS;
+            if radius < 0.0 || radius >= length as f32 {
+                continue;
+            }
+            let phi = dy.atan2(dx).rem_euclid(2.0 * PI) / (2.0 * PI) * strips as f32;
+            let strip = phi.round() as usize % strips;
+            let y = radius as usize;
+
+            let across = (phi - phi.round()).abs() * 2.0 * PI / strips as f32 * (radius + FUNNEL_INNER_RADIUS);
+            let along = radius.fract() - 0.5;
+            if across.abs() * 2.0 > FUNNEL_DOT_SIZE || along.abs() * 2.0 > FUNNEL_DOT_SIZE {
+                continue;
+            }
+            canvas.set(px, py, led_rgb(led_strip, strip, y));
+        }
+    }
+    canvas
+}
+
+pub fn write_png(path: &Path, canvas: &Canvas) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, canvas.width as u32, canvas.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&canvas.data).map_err(io::Error::other)
+}
+
+/// Animated GIF, every frame is shown for its own delay.
+pub struct GifWriter {
+    encoder: gif::Encoder<BufWriter<File>>,
+}
+
+impl GifWriter {
+    pub fn create(path: &Path, width: usize, height: usize) -> io::Result<GifWriter> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &[])
+            .map_err(io::Error::other)?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+        Ok(GifWriter { encoder })
+    }
+
+    pub fn add_frame(&mut self, canvas: &Canvas, delay_centis: u16) -> io::Result<()> {
+        let mut frame = gif::Frame::from_rgb_speed(canvas.width as u16, canvas.height as u16, &canvas.data, 10);
+        frame.delay = delay_centis;
+        self.encoder.write_frame(&frame).map_err(io::Error::other)
+    }
+}
@@ -0,0 +1,299 @@
+use std::{cell::Cell, convert::Infallible, rc::Rc};
+
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use fugit::MicrosDurationU64;
+
+use lumio::{
+    button::{Button, ButtonState},
+    command::{LineReader, Overflow},
+    conf::{LONG_PRESS_TIME, SPI_BAUD_RATE},
+    hardware::{Hardware, Instant},
+    layout::Layout,
+    ledstrip::LEDStrip,
+    random::Random,
+    showtimer::ShowTimer,
+    stream::{LiveFrame, StreamDecoder},
+    text::Line,
+};
+
+/// The random seed the firmware uses in `Interface::new()`.
+pub const FIRMWARE_SEED: u64 = 423434859;
+
+const SHORT_PRESS_TIME: MicrosDurationU64 = MicrosDurationU64::millis(100);
+
+
+/// A button line that is pulled low while the button is held down.
+#[derive(Clone)]
+pub struct SimPin {
+    low: Rc<Cell<bool>>,
+}
+
+impl InputPin for SimPin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> { Ok(!self.low.get()) }
+    fn is_low(&self) -> Result<bool, Infallible> { Ok(self.low.get()) }
+}
+
+/// An indicator LED.
+#[derive(Clone)]
+pub struct SimLed {
+    on: Rc<Cell<bool>>,
+}
+
+impl OutputPin for SimLed {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.on.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.on.set(true);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SimButton {
+    /// The first button, handled by the show timer.
+    Next,
+    /// The second button, passed to the running show.
+    Show,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Press {
+    Short,
+    Long,
+}
+
+struct ButtonLine {
+    pin: SimPin,
+    release_time: Option<Instant>,
+}
+
+impl ButtonLine {
+    fn new() -> ButtonLine {
+        ButtonLine { pin: SimPin { low: Rc::new(Cell::new(false)) }, release_time: None }
+    }
+
+    fn update(&mut self, now: Instant) {
+        let held = self.release_time.is_some_and(|release| now < release);
+        self.pin.low.set(held);
+        if !held {
+            self.release_time = None;
+        }
+    }
+}
+
+
+/// The installation without the hardware.
+///
+/// Time is simulated: every written frame advances the clock by the time the
+/// two SPI transfers take on the real board, or by a fixed transfer time.
+/// Waiting for the next frame takes no time, so the frames are as far apart as
+/// the `FrameClock` of the show runner says, or the transfer time if that is
+/// longer.  Button presses are fed through the same `Button` and `ShowTimer`
+/// decoding as on the board.
+pub struct Simulator {
+    led_strip: LEDStrip,
+    random: Random,
+    showtimer: ShowTimer<SimPin, SimLed>,
+    button: Button<SimPin>,
+    lines: [ButtonLine; 2],
+    leds: [Rc<Cell<bool>>; 2],
+    now: Instant,
+    transfer_time: Option<MicrosDurationU64>,
+    frames: u64,
+    log: Vec<u8>,
+    received: LineReader,
+    stream: StreamDecoder,
+}
+
+impl Simulator {
+    pub fn new(seed: u64) -> Simulator {
+        Simulator::with_layout(seed, Layout::FLOWER)
+    }
+
+    pub fn with_layout(seed: u64, layout: Layout) -> Simulator {
+        let lines = [ButtonLine::new(), ButtonLine::new()];
+        let leds = [Rc::new(Cell::new(false)), Rc::new(Cell::new(false))];
+        let now = Instant::from_ticks(0);
+        Simulator {
+            led_strip: LEDStrip::new(layout),
+            random: Random::new(seed),
+            showtimer: ShowTimer::new(
+                Button::new(lines[0].pin.clone()),
+                SimLed { on: leds[0].clone() }
+            ),
+            button: Button::new(lines[1].pin.clone()),
+            lines,
+            leds,
+            now,
+            transfer_time: None,
+            frames: 0,
+            log: Vec::new(),
+            received: LineReader::new(),
+            stream: StreamDecoder::new(layout),
+        }
+    }
+
+    /// Use a fixed time to write out a frame instead of the estimated SPI
+    /// transfer time.  It does not set the frame rate.
+    pub fn set_transfer_time(&mut self, transfer_time: MicrosDurationU64) {
+        self.transfer_time = Some(transfer_time);
+    }
+
+    pub fn press(&mut self, button: SimButton, press: Press) {
+        let duration = match press {
+            Press::Short => SHORT_PRESS_TIME,
+            Press::Long => LONG_PRESS_TIME + SHORT_PRESS_TIME,
+        };
+        let line = &mut self.lines[button as usize];
+        line.release_time = Some(self.now + duration);
+        line.update(self.now);
+    }
+
+    /// The indicator LEDs of the show timer and of the shows.
+    pub fn indicators(&self) -> (bool, bool) {
+        (self.leds[0].get(), self.leds[1].get())
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Bytes from the host, as if sent over USB.  Replies to uploads go to the
+    /// log.
+    pub fn send(&mut self, bytes: &[u8]) {
+        let received = &mut self.received;
+        self.stream.push(bytes, |text| received.push(text));
+        self.log.extend_from_slice(self.stream.take_replies().as_bytes());
+    }
+
+    /// The latest frame streamed by the host, once, instead of writing it
+    /// onto the LED strip.
+    pub fn take_live_frame(&mut self) -> Option<&LiveFrame> {
+        self.stream.take_frame()
+    }
+
+    /// As if the host closed the port.
+    pub fn close_port(&mut self) {
+        self.stream.reset();
+    }
+
+    pub fn take_log(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.log)
+    }
+
+    fn update_buttons(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.update(self.now);
+        }
+    }
+
+    fn spi_transfer_time(&mut self) -> MicrosDurationU64 {
+        let bytes = (self.led_strip.dump_0().len() + self.led_strip.dump_1().len()) as u64;
+        MicrosDurationU64::micros(bytes * 8 * 1_000_000 / SPI_BAUD_RATE as u64)
+    }
+}
+
+impl Hardware for Simulator {
+    fn get_time(&self) -> Instant {
+        self.now
+    }
+
+    fn random(&mut self) -> &mut Random { &mut self.random }
+
+    fn led_strip(&mut self) -> &mut LEDStrip { &mut self.led_strip }
+
+    fn write_frame(&mut self) {
+        self.led_strip.process();
+        let transfer_time = match self.transfer_time {
+            Some(transfer_time) => transfer_time,
+            None => self.spi_transfer_time(),
+        };
+        self.now += transfer_time;
+        self.frames += 1;
+    }
+
+    fn do_next(&mut self) -> bool {
+        self.update_buttons();
+        self.showtimer.do_next(self.now)
+    }
+
+    fn auto_show(&mut self) -> bool {
+        self.showtimer.auto_show()
+    }
+
+    fn set_auto_show(&mut self, on: bool) {
+        self.showtimer.set_auto_show(on);
+    }
+
+    fn button_state(&mut self) -> ButtonState {
+        self.update_buttons();
+        self.button.state(self.now)
+    }
+
+    fn led_on(&mut self) {
+        self.leds[1].set(true);
+    }
+
+    fn led_off(&mut self) {
+        self.leds[1].set(false);
+    }
+
+    fn delay_ms(&mut self, delay: u32) {
+        self.now += MicrosDurationU64::millis(delay as u64);
+    }
+
+    fn wait_until(&mut self, time: Instant) {
+        self.now = self.now.max(time);
+    }
+
+    fn log(&mut self, message: &[u8]) {
+        self.log.extend_from_slice(message);
+    }
+
+    fn read_line(&mut self) -> Option<Result<Line, Overflow>> {
+        self.received.read_line()
+    }
+
+    fn has_live_frame(&self) -> bool {
+        self.stream.has_frame()
+    }
+
+    fn write_live_frame(&mut self) -> bool {
+        match self.stream.take_frame() {
+            Some(frame) => {
+                frame.write_to(&mut self.led_strip);
+                true
+            }
+            None => false
+        }
+    }
+}
+
+/// Parses `flower`, `half-flower` or `STRIPSxLENGTH`, the latter with a
+/// trailing `-open` for strips that do not form a closed circle.
+pub fn parse_layout(text: &str) -> Result<Layout, String> {
+    match text {
+        "flower" => return Ok(Layout::FLOWER),
+        "half-flower" => return Ok(Layout::HALF_FLOWER),
+        _ => {}
+    }
+    let (size, wrap) = match text.strip_suffix("-open") {
+        Some(size) => (size, false),
+        None => (text, true),
+    };
+    let invalid = || format!("invalid layout {text}, expected e.g. 24x60 or 12x60-open");
+    let (strips, length) = size.split_once('x').ok_or_else(invalid)?;
+    let strips: usize = strips.parse().map_err(|_| invalid())?;
+    let length: usize = length.parse().map_err(|_| invalid())?;
+    if !Layout::fits(strips, length) {
+        return Err(format!("layout {text} does not fit into the LED buffers"));
+    }
+    Ok(Layout::new(strips, length, wrap))
+}
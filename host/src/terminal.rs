@@ -0,0 +1,213 @@
+use std::{
+    io::{self, BufWriter, Stdout, Write},
+    time::Duration,
+};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Color as TermColor, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+
+use lumio::{
+    button::ButtonState,
+    command::Overflow,
+    hardware::{Hardware, Instant},
+    ledstrip::LEDStrip,
+    random::Random,
+    text::Line,
+};
+
+use crate::display::display_rgb;
+use crate::simulator::{Press, SimButton, Simulator};
+
+const DRAW_INTERVAL: Duration = Duration::from_millis(20);
+const STRIP_WIDTH: usize = 2;
+
+pub const KEY_HELP: &str = "n/space: next  a: auto on/off  b: button  l: long press  q: quit";
+
+
+/// The simulator drawing the strips as columns of truecolor half blocks.
+///
+/// Two LEDs share one character cell, LED 0 of every strip is at the bottom.
+/// The simulation is paced to the wall clock, the terminal is only redrawn
+/// every `DRAW_INTERVAL`.
+pub struct Terminal {
+    sim: Simulator,
+    out: BufWriter<Stdout>,
+    started: std::time::Instant,
+    last_draw: Option<std::time::Instant>,
+    show_name: String,
+    quit: bool,
+}
+
+impl Terminal {
+    pub fn new(sim: Simulator) -> io::Result<Terminal> {
+        let mut out = BufWriter::new(io::stdout());
+        terminal::enable_raw_mode()?;
+        queue!(out, terminal::EnterAlternateScreen, cursor::Hide, terminal::Clear(terminal::ClearType::All))?;
+        out.flush()?;
+        Ok(Terminal {
+            sim,
+            out,
+            started: std::time::Instant::now(),
+            last_draw: None,
+            show_name: String::new(),
+            quit: false,
+        })
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    fn poll_keys(&mut self) {
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            let Ok(Event::Key(KeyEvent { code, modifiers, kind: KeyEventKind::Press, .. })) = event::read() else {
+                continue;
+            };
+            match code {
+                KeyCode::Char('n') | KeyCode::Char(' ') => self.sim.press(SimButton::Next, Press::Short),
+                KeyCode::Char('a') => self.sim.press(SimButton::Next, Press::Long),
+                KeyCode::Char('b') => self.sim.press(SimButton::Show, Press::Short),
+                KeyCode::Char('l') => self.sim.press(SimButton::Show, Press::Long),
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+                KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+                _ => {}
+            }
+        }
+    }
+
+    fn pace(&mut self) {
+        let simulated = Duration::from_micros(self.sim.get_time().duration_since_epoch().to_micros());
+        let elapsed = self.started.elapsed();
+        if simulated > elapsed {
+            std::thread::sleep(simulated - elapsed);
+        }
+    }
+
+    fn maybe_draw(&mut self) {
+        let now = std::time::Instant::now();
+        if self.last_draw.is_some_and(|last| now - last < DRAW_INTERVAL) {
+            return;
+        }
+        self.last_draw = Some(now);
+        let _ = self.draw();
+    }
+
+    fn draw(&mut self) -> io::Result<()> {
+        let log = self.sim.take_log();
+        if let Some(line) = String::from_utf8_lossy(&log).lines().last() {
+            self.show_name = line.to_string();
+        }
+
+        let layout = self.sim.led_strip().layout();
+        let rows = layout.length().div_ceil(2);
+        for row in 0..rows {
+            let lower = (rows - 1 - row) * 2;
+            queue!(self.out, cursor::MoveTo(0, row as u16))?;
+            for strip in 0..layout.strips() {
+                let top = led_color(self.sim.led_strip(), strip, lower + 1);
+                let bottom = led_color(self.sim.led_strip(), strip, lower);
+                queue!(
+                    self.out,
+                    SetForegroundColor(top),
+                    SetBackgroundColor(bottom),
+                    Print("▀".repeat(STRIP_WIDTH))
+                )?;
+            }
+            queue!(self.out, ResetColor)?;
+        }
+
+        let (auto_show, show_led) = self.sim.indicators();
+        let seconds = self.sim.get_time().duration_since_epoch().to_millis() as f32 / 1000.0;
+        queue!(
+            self.out,
+            cursor::MoveTo(0, rows as u16 + 1),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            Print(format!(
+                "{:<20} auto {}  led {}  {:8.1} s  frame {}",
+                self.show_name,
+                indicator(auto_show),
+                indicator(show_led),
+                seconds,
+                self.sim.frames()
+            )),
+            cursor::MoveTo(0, rows as u16 + 2),
+            Print(KEY_HELP),
+        )?;
+        self.out.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = queue!(self.out, ResetColor, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = self.out.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Hardware for Terminal {
+    fn get_time(&self) -> Instant {
+        self.sim.get_time()
+    }
+
+    fn random(&mut self) -> &mut Random { self.sim.random() }
+
+    fn led_strip(&mut self) -> &mut LEDStrip { self.sim.led_strip() }
+
+    fn write_frame(&mut self) {
+        self.sim.write_frame();
+        self.pace();
+        self.maybe_draw();
+    }
+
+    fn do_next(&mut self) -> bool {
+        self.poll_keys();
+        self.sim.do_next() || self.quit
+    }
+
+    fn auto_show(&mut self) -> bool { self.sim.auto_show() }
+
+    fn set_auto_show(&mut self, on: bool) { self.sim.set_auto_show(on) }
+
+    fn button_state(&mut self) -> ButtonState { self.sim.button_state() }
+
+    fn led_on(&mut self) { self.sim.led_on() }
+
+    fn led_off(&mut self) { self.sim.led_off() }
+
+    fn delay_ms(&mut self, delay: u32) {
+        self.sim.delay_ms(delay);
+        self.pace();
+    }
+
+    fn wait_until(&mut self, time: Instant) {
+        self.sim.wait_until(time);
+        self.pace();
+    }
+
+    fn log(&mut self, message: &[u8]) { self.sim.log(message) }
+
+    fn read_line(&mut self) -> Option<Result<Line, Overflow>> { self.sim.read_line() }
+
+    fn has_live_frame(&self) -> bool { self.sim.has_live_frame() }
+
+    fn write_live_frame(&mut self) -> bool { self.sim.write_live_frame() }
+}
+
+fn led_color(led_strip: &LEDStrip, strip: usize, y: usize) -> TermColor {
+    let layout = led_strip.layout();
+    if y >= layout.length() || !layout.has_led(strip, y) {
+        return TermColor::Black;
+    }
+    let [r, g, b] = display_rgb(led_strip, led_strip.output(layout.index(strip, y)));
+    TermColor::Rgb { r, g, b }
+}
+
+fn indicator(on: bool) -> &'static str {
+    if on { "●" } else { "○" }
+}
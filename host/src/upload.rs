@@ -0,0 +1,100 @@
+//! Reference encoder of the compressed frame upload, see `lumio::upload`
+
+use lumio::{
+    led::Color,
+    upload::{crc16, KEEP, KEY, LITERAL, MAX_OP_LEDS, RUN, SHIFT, START}
+};
+
+/// Encodes the frames of a stream, each relative to the one before.
+pub struct Encoder {
+    /// The frame sent last, none before the next key frame.
+    previous: Option<Vec<Color>>,
+    sequence: u8,
+}
+
+impl Encoder {
+    pub fn new() -> Encoder {
+        Encoder { previous: None, sequence: 0 }
+    }
+
+    /// Sends the next frame as a key frame, e.g. after a NAK.
+    pub fn restart(&mut self) {
+        self.previous = None;
+    }
+
+    /// The sequence number of the next packet.
+    pub fn sequence(&self) -> u8 {
+        self.sequence
+    }
+
+    /// The packet that uploads `colors`.
+    pub fn encode(&mut self, colors: &[Color]) -> Vec<u8> {
+        let previous = self.previous.take().filter(|previous| previous.len() == colors.len());
+        let flags = if previous.is_none() { KEY } else { 0 };
+        let ops = encode_ops(colors, previous.as_deref());
+
+        let mut packet = vec![flags, self.sequence];
+        packet.extend((colors.len() as u16).to_be_bytes());
+        packet.extend((ops.len() as u16).to_be_bytes());
+        packet.extend(ops);
+        packet.extend(crc16(0xffff, &packet).to_be_bytes());
+        packet.insert(0, START);
+
+        self.previous = Some(colors.to_vec());
+        self.sequence = self.sequence.wrapping_add(1);
+        packet
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Encoder {
+        Encoder::new()
+    }
+}
+
+/// The brightness change of `old` to `new`, if all channels changed by it.
+fn shift(old: Color, new: Color) -> Option<i8> {
+    let delta = new.r as i16 - old.r as i16;
+    let fits = delta != 0 && new.g as i16 - old.g as i16 == delta && new.b as i16 - old.b as i16 == delta;
+    fits.then(|| i8::try_from(delta).ok()).flatten()
+}
+
+/// The ops that turn `previous`, or black for key frames, into `colors`,
+/// greedily: unchanged LEDs are kept, equal ones run, small changes shift.
+fn encode_ops(colors: &[Color], previous: Option<&[Color]>) -> Vec<u8> {
+    let kept = |i: usize| previous.is_some_and(|previous| previous[i] == colors[i]);
+    let shifted = |i: usize| previous.and_then(|previous| shift(previous[i], colors[i]));
+    let running = |i: usize| i + 1 < colors.len() && colors[i] == colors[i + 1];
+    // The LEDs from `i` on for which `f` holds, at most `MAX_OP_LEDS`
+    let count = |i: usize, f: &dyn Fn(usize) -> bool| {
+        (i..colors.len()).take(MAX_OP_LEDS).take_while(|&j| f(j)).count()
+    };
+
+    let mut ops = Vec::new();
+    let mut i = 0;
+    while i < colors.len() {
+        let n;
+        if kept(i) {
+            n = count(i, &kept);
+            if i + n == colors.len() {
+                // The LEDs after the last op keep their colour anyway
+                break;
+            }
+            ops.push(KEEP | (n - 1) as u8);
+        } else if running(i) {
+            n = count(i, &|j| colors[j] == colors[i]);
+            let Color { r, g, b } = colors[i];
+            ops.extend([RUN | (n - 1) as u8, r, g, b]);
+        } else if shifted(i).is_some() {
+            n = count(i, &|j| shifted(j).is_some() && !running(j));
+            ops.push(SHIFT | (n - 1) as u8);
+            ops.extend((i..i + n).map(|j| shifted(j).unwrap() as u8));
+        } else {
+            n = count(i, &|j| !kept(j) && !running(j) && shifted(j).is_none());
+            ops.push(LITERAL | (n - 1) as u8);
+            ops.extend(colors[i..i + n].iter().flat_map(|c| [c.r, c.g, c.b]));
+        }
+        i += n;
+    }
+    ops
+}
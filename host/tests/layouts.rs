@@ -0,0 +1,87 @@
+//! Every show has to cope with installations other than the full flower
+
+use fugit::MicrosDurationU64;
+
+use lumio::{
+    conf::FRAME_RATE,
+    frameclock::FrameClock,
+    hardware::Hardware,
+    layout::Layout,
+    programme::{Programme, SHOW_NUM},
+    protocol::{Frame, Protocol},
+    show::{run_frame, Params}
+};
+use lumio_host::simulator::{parse_layout, Press, SimButton, Simulator, FIRMWARE_SEED};
+
+const FRAMES: usize = 600;
+
+fn run_all_shows(layout: Layout) {
+    for index in 0..SHOW_NUM {
+        let mut programme = Programme::new();
+        let mut shows = programme.shows::<Simulator>();
+        let show = &mut shows[index];
+
+        let mut sim = Simulator::with_layout(FIRMWARE_SEED, layout);
+        sim.set_transfer_time(MicrosDurationU64::millis(10));
+
+        show.init(&mut sim, Params::new());
+        let mut clock = FrameClock::new(FRAME_RATE);
+        for frame in 0..FRAMES {
+            match frame {
+                100 | 400 => sim.press(SimButton::Show, Press::Short),
+                200 => sim.press(SimButton::Show, Press::Long),
+                _ => {}
+            }
+            run_frame(*show, &mut sim, &mut clock);
+        }
+        show.teardown(&mut sim);
+
+        let half = layout.physical_leds() / 2;
+        let led_strip = sim.led_strip();
+        assert_eq!(led_strip.dump_0().len(), Frame::new(Protocol::Apa102, half).len(), "{}", show.name());
+        assert_eq!(
+            led_strip.dump_1().len(),
+            Frame::new(Protocol::Apa102, layout.physical_leds() - half).len(),
+            "{}",
+            show.name()
+        );
+    }
+}
+
+#[test]
+fn shows_run_on_the_half_flower() {
+    run_all_shows(Layout::HALF_FLOWER);
+}
+
+#[test]
+fn shows_run_on_short_strips() {
+    run_all_shows(Layout::new(6, 8, true));
+}
+
+#[test]
+fn shows_run_on_a_few_long_strips() {
+    run_all_shows(Layout::new(3, 200, false));
+}
+
+#[test]
+fn shows_run_on_repaired_strips() {
+    run_all_shows(
+        Layout::FLOWER
+            .with_strip_length(3, 57)
+            .with_strip_length(17, 58)
+            .with_dead_led(5, 0)
+            .with_dead_led(5, 30)
+            .with_dead_led(20, 59),
+    );
+    run_all_shows(Layout::HALF_FLOWER.with_strip_length(4, 64));
+}
+
+#[test]
+fn layouts_parse() {
+    assert_eq!(parse_layout("flower"), Ok(Layout::FLOWER));
+    assert_eq!(parse_layout("12x60-open"), Ok(Layout::HALF_FLOWER));
+    assert_eq!(parse_layout("6x8"), Ok(Layout::new(6, 8, true)));
+    assert!(parse_layout("25x10").is_err());
+    assert!(parse_layout("24x61").is_err());
+    assert!(parse_layout("twelve").is_err());
+}
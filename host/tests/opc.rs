@@ -0,0 +1,86 @@
+//! Open Pixel Control over USB, as a client sends it
+
+use fugit::MicrosDurationU64;
+
+use lumio::{
+    conf::PLAYLIST,
+    hardware::Hardware,
+    led::{Color, BLACK},
+    layout::Layout,
+    programme::Programme,
+    show::run_next
+};
+use lumio_host::simulator::{Simulator, FIRMWARE_SEED};
+
+/// A message the way the usual clients send it, e.g. `put_pixels()` of the
+/// Python client of openpixelcontrol.org.
+fn message(channel: u8, command: u8, data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![channel, command];
+    bytes.extend((data.len() as u16).to_be_bytes());
+    bytes.extend(data);
+    bytes
+}
+
+fn gray(level: u8) -> Color {
+    Color { r: level, g: level, b: level }
+}
+
+/// What a client sends to a 3x4 flower: the switch, a system exclusive
+/// message, a whole frame and one strip.
+fn session() -> Vec<u8> {
+    let mut bytes = b"opc\n".to_vec();
+    bytes.extend(message(0, 255, &[0x00, 0x01, 0x10, 0x20]));
+    bytes.extend(message(0, 0, &(0..12).flat_map(|i| [i * 10; 3]).collect::<Vec<_>>()));
+    bytes.extend(message(2, 0, &[255; 3 * 5]));
+    bytes
+}
+
+#[test]
+fn messages_set_the_leds_in_any_pieces() {
+    for chunk in [1, 2, 3, 7, 11] {
+        let mut sim = Simulator::with_layout(FIRMWARE_SEED, Layout::new(3, 4, true));
+        let mut frames = Vec::new();
+        for piece in session().chunks(chunk) {
+            sim.send(piece);
+            if let Some(frame) = sim.take_live_frame() {
+                frames.push(frame.colors().to_vec());
+            }
+        }
+        let first: Vec<_> = (0..12).map(|i| gray(i * 10)).collect();
+        let mut second = first.clone();
+        second[4..8].fill(gray(255));
+        assert!(frames == [first, second], "chunk {chunk}");
+        assert_eq!(sim.read_line().unwrap().unwrap().as_bytes(), b"opc");
+    }
+}
+
+#[test]
+fn closing_the_port_ends_opc() {
+    let mut sim = Simulator::with_layout(FIRMWARE_SEED, Layout::new(3, 4, true));
+    sim.send(&session());
+    sim.close_port();
+    sim.send(b"status\n");
+    assert_eq!(sim.read_line().unwrap().unwrap().as_bytes(), b"opc");
+    assert_eq!(sim.read_line().unwrap().unwrap().as_bytes(), b"status");
+
+    // Back in OPC the frame starts black
+    sim.send(b"opc\n");
+    sim.send(&message(1, 0, &[255; 3]));
+    let frame = sim.take_live_frame().unwrap();
+    assert!(frame.colors()[0] == gray(255));
+    assert!(frame.colors()[1..].iter().all(|&c| c == BLACK));
+}
+
+#[test]
+fn opc_frames_take_over_like_streamed_ones() {
+    let mut programme = Programme::new();
+    let mut shows = programme.shows::<Simulator>();
+    let mut playlist = PLAYLIST;
+    let mut sim = Simulator::new(FIRMWARE_SEED);
+    sim.set_transfer_time(MicrosDurationU64::millis(20));
+
+    sim.send(b"opc\n");
+    sim.send(&message(0, 0, &[255, 0, 0]));
+    run_next(&mut playlist, &mut shows, &mut sim);
+    assert_eq!(sim.take_log(), b"snow\nok\nlive\n");
+}
@@ -0,0 +1,159 @@
+//! The playlist of the firmware has to match the programme
+
+use fugit::MicrosDurationU64;
+
+use lumio::{
+    conf::{AUTO_SHOW_DELAY, FRAME_RATE, PLAYLIST},
+    hardware::Hardware,
+    led::{Color, RED, WHITE},
+    playlist::{Entry, Playlist},
+    programme::Programme,
+    show::{run_live, run_next, Ending}
+};
+use lumio_host::simulator::{SimButton, Press, Simulator, FIRMWARE_SEED};
+
+#[test]
+fn every_entry_names_a_show() {
+    let mut programme = Programme::new();
+    let shows = programme.shows::<Simulator>();
+    for entry in PLAYLIST.entries() {
+        assert!(shows.iter().any(|show| show.name() == entry.show()), "no show named {}", entry.show());
+    }
+}
+
+#[test]
+fn entries_set_the_colours_of_their_show() {
+    let sky = Color { r: 1, g: 2, b: 3 };
+    let entry = Entry::new("eu_stars").with_colors(sky, RED);
+    let mut programme = Programme::new();
+    let shows = programme.shows::<Simulator>();
+    let mut sim = Simulator::new(FIRMWARE_SEED);
+    let index = shows.iter().position(|show| show.name() == entry.show()).unwrap();
+
+    shows[index].init(&mut sim, entry.params());
+    let num_leds = sim.led_strip().layout().num_leds();
+    assert!((0..num_leds).all(|i| sim.led_strip().led(i).current() == sky));
+}
+
+#[test]
+fn selected_shows_run_as_their_entry_says() {
+    static ENTRIES: [Entry; 2] = [
+        Entry::new("snow"),
+        Entry::new("eu_stars")
+            .with_colors(Color { r: 96, g: 0, b: 0 }, WHITE)
+            .with_duration(MicrosDurationU64::secs(1))
+    ];
+    let mut programme = Programme::new();
+    let mut shows = programme.shows::<Simulator>();
+    let mut playlist = Playlist::new(&ENTRIES);
+    let mut sim = Simulator::new(FIRMWARE_SEED);
+    sim.set_transfer_time(MicrosDurationU64::millis(20));
+
+    sim.send(b"select eu_stars\n");
+    run_next(&mut playlist, &mut shows, &mut sim);
+    assert_eq!(sim.take_log(), b"snow\nok\neu_stars\n");
+    let time = sim.get_time().duration_since_epoch().to_millis();
+    assert!((1_000..1_100).contains(&time), "{time}");
+    // A red sky instead of the blue one of the show
+    let num_leds = sim.led_strip().layout().num_leds();
+    let red = (0..num_leds).filter(|&i| sim.led_strip().output(i).r > sim.led_strip().output(i).b).count();
+    assert!(red > num_leds / 2, "{red}");
+}
+
+#[test]
+fn the_next_button_moves_through_the_playlist() {
+    let mut programme = Programme::new();
+    let mut shows = programme.shows::<Simulator>();
+    let mut playlist = PLAYLIST;
+    let mut sim = Simulator::new(FIRMWARE_SEED);
+
+    for entry in PLAYLIST.entries().iter().take(3) {
+        sim.press(SimButton::Next, Press::Short);
+        run_next(&mut playlist, &mut shows, &mut sim);
+        assert_eq!(sim.take_log(), format!("{}\n", entry.show()).into_bytes());
+    }
+    assert!(sim.get_time().ticks() > 0);
+}
+
+#[test]
+fn entries_run_for_their_duration() {
+    const ENTRIES: &[Entry] = &[
+        Entry::new("sine").with_duration(MicrosDurationU64::secs(5)),
+        Entry::new("snake").until_finished(),
+        Entry::new("sine").with_duration(MicrosDurationU64::secs(2)),
+    ];
+    let mut programme = Programme::new();
+    let mut shows = programme.shows::<Simulator>();
+    let mut playlist = Playlist::new(ENTRIES);
+    let mut sim = Simulator::new(FIRMWARE_SEED);
+    sim.set_transfer_time(MicrosDurationU64::millis(20));
+
+    let mut run = |sim: &mut Simulator| {
+        let start = sim.get_time();
+        run_next(&mut playlist, &mut shows, sim);
+        (sim.get_time() - start).to_millis()
+    };
+    assert!((5_000..5_040).contains(&run(&mut sim)));
+    // One snake up the strips of 60 LEDs
+    assert!((1_200..1_300).contains(&run(&mut sim)));
+    assert!((2_000..2_040).contains(&run(&mut sim)));
+}
+
+#[test]
+fn commands_over_usb_pick_the_shows() {
+    let mut programme = Programme::new();
+    let mut shows = programme.shows::<Simulator>();
+    let mut playlist = PLAYLIST;
+    let mut sim = Simulator::new(FIRMWARE_SEED);
+    sim.set_transfer_time(MicrosDurationU64::millis(20));
+
+    sim.send(b"select planets\nbrightness 99\r\nauto off\nstatus\nselect nothing\nauto on\nprev\n");
+    run_next(&mut playlist, &mut shows, &mut sim);
+    let log = String::from_utf8(sim.take_log()).unwrap();
+    assert_eq!(
+        log,
+        "snow\nok\nplanets\nok\nok\nshow planets auto off brightness 99\n\
+         error: no show named nothing\nok\nok\nsnow\n"
+    );
+    // All the lines that came at once are carried out in the first frame of
+    // planets, then snow runs for AUTO_SHOW_DELAY
+    assert_eq!(sim.frames(), 2 + AUTO_SHOW_DELAY.to_secs() * FRAME_RATE as u64);
+
+    sim.send(b"list\nnext\n");
+    run_next(&mut playlist, &mut shows, &mut sim);
+    let log = String::from_utf8(sim.take_log()).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines[0], "sine");
+    assert_eq!(lines.len(), 1 + shows.len() + 2);
+    assert!(lines.contains(&"fireworks"));
+    assert_eq!(lines[lines.len() - 2..], ["ok", "ok"]);
+}
+
+#[test]
+fn frames_streamed_over_usb_take_over_until_they_stop() {
+    let mut programme = Programme::new();
+    let mut shows = programme.shows::<Simulator>();
+    let mut playlist = PLAYLIST;
+    let mut sim = Simulator::new(FIRMWARE_SEED);
+    sim.set_transfer_time(MicrosDurationU64::millis(20));
+
+    // TPM2, the first LED red
+    sim.send(&[0xc9, 0xda, 0x00, 0x03, 0xff, 0x00, 0x00, 0x36]);
+    run_next(&mut playlist, &mut shows, &mut sim);
+    assert_eq!(sim.take_log(), b"snow\nlive\n");
+    let time = sim.get_time().duration_since_epoch().to_millis();
+    assert!((5_000..5_100).contains(&time), "{time}");
+}
+
+#[test]
+fn commands_are_answered_while_live() {
+    let mut programme = Programme::new();
+    let shows = programme.shows::<Simulator>();
+    let mut sim = Simulator::new(FIRMWARE_SEED);
+
+    sim.send(b"status\nselect planets\n");
+    let ending = run_live(&shows, &mut sim);
+    assert_eq!(ending, Ending::Select(shows.iter().position(|show| show.name() == "planets").unwrap()));
+    assert_eq!(sim.take_log(), b"live\nshow live auto on brightness 255\nok\n");
+    assert_eq!(sim.frames(), 1);
+}
@@ -0,0 +1,131 @@
+//! Golden-frame snapshots of every show of the programme
+//!
+//! Each show runs for a fixed number of frames with the firmware's random seed,
+//! a fixed frame time and a scripted sequence of button presses.  The bytes
+//! that go out to the SPI busses are hashed frame by frame and compared against
+//! `tests/snapshots/<show>.snap`.
+//!
+//! After an intended change of a show's output, bless the new snapshots with
+//!
+//!     LUMIO_BLESS=1 cargo test --test snapshots
+
+use std::{fmt::Write as _, fs, path::PathBuf};
+
+use fugit::MicrosDurationU64;
+
+use lumio::{
+    conf::FRAME_RATE, frameclock::FrameClock, hardware::Hardware, programme::{Programme, SHOW_NUM}, show::{run_frame, Params}
+};
+use lumio_host::simulator::{Press, SimButton, Simulator, FIRMWARE_SEED};
+
+const FRAMES: usize = 400;
+const CHECKPOINT_EVERY: usize = 10;
+/// The simulated time to write out a frame, the frames are `FRAME_RATE` apart.
+const TRANSFER_TIME: MicrosDurationU64 = MicrosDurationU64::millis(10);
+
+const BUTTON_SCRIPT: &[(usize, SimButton, Press)] = &[
+    (100, SimButton::Show, Press::Short),
+    (200, SimButton::Show, Press::Long),
+    (300, SimButton::Show, Press::Short),
+];
+
+/// 64 bit FNV-1a, chosen because it is stable across Rust releases.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+fn snapshot_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{name}.snap"))
+}
+
+fn record(index: usize) -> (&'static str, String) {
+    let mut programme = Programme::new();
+    let mut shows = programme.shows::<Simulator>();
+    let show = &mut shows[index];
+
+    let mut sim = Simulator::new(FIRMWARE_SEED);
+    sim.set_transfer_time(TRANSFER_TIME);
+
+    let mut snapshot = format!(
+        "# show {}, seed {FIRMWARE_SEED}, {FRAME_RATE} fps, {} ms per transfer\n",
+        show.name(),
+        TRANSFER_TIME.to_millis()
+    );
+    let mut hash = Fnv::new();
+
+    show.init(&mut sim, Params::new());
+    let mut clock = FrameClock::new(FRAME_RATE);
+    for frame in 0..FRAMES {
+        for &(_, button, press) in BUTTON_SCRIPT.iter().filter(|(at, _, _)| *at == frame) {
+            sim.press(button, press);
+        }
+        run_frame(*show, &mut sim, &mut clock);
+
+        hash.write(sim.led_strip().dump_0());
+        hash.write(sim.led_strip().dump_1());
+        if frame % CHECKPOINT_EVERY == 0 {
+            let _ = writeln!(snapshot, "{frame} {:016x}", hash.0);
+        }
+    }
+    show.teardown(&mut sim);
+
+    (show.name(), snapshot)
+}
+
+fn first_difference(expected: &str, actual: &str) -> String {
+    expected
+        .lines()
+        .zip(actual.lines())
+        .find(|(e, a)| e != a)
+        .map(|(e, a)| format!("expected `{e}`, got `{a}`"))
+        .unwrap_or_else(|| "different number of checkpoints".to_string())
+}
+
+#[test]
+fn shows_match_snapshots() {
+    let bless = std::env::var_os("LUMIO_BLESS").is_some();
+    let mut failures = Vec::new();
+
+    for index in 0..SHOW_NUM {
+        let (name, actual) = record(index);
+        let path = snapshot_path(name);
+
+        if bless {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &actual).unwrap();
+            continue;
+        }
+
+        match fs::read_to_string(&path) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!("{name}: {}", first_difference(&expected, &actual))),
+            Err(_) => failures.push(format!("{name}: no snapshot at {}", path.display())),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "show output changed, bless with LUMIO_BLESS=1 if intended:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn shows_are_deterministic() {
+    for index in 0..SHOW_NUM {
+        let (name, first) = record(index);
+        let (_, second) = record(index);
+        assert_eq!(first, second, "{name} differs between two runs");
+    }
+}
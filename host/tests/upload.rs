@@ -0,0 +1,79 @@
+//! Round trips of the compressed frame upload: frames of the shows, encoded by
+//! the reference encoder, decoded by the firmware in the simulator
+
+use fugit::MicrosDurationU64;
+
+use lumio::{
+    conf::FRAME_RATE,
+    frameclock::FrameClock,
+    hardware::Hardware,
+    led::Color,
+    programme::{Programme, SHOW_NUM},
+    show::{run_frame, Params},
+    upload::{ACK, NAK}
+};
+use lumio_host::{simulator::{Simulator, FIRMWARE_SEED}, upload::Encoder};
+
+const FRAMES: usize = 100;
+
+/// The frames of show `index` after each rendering.
+fn frames(index: usize) -> Vec<Vec<Color>> {
+    let mut programme = Programme::new();
+    let mut shows = programme.shows::<Simulator>();
+    let mut sim = Simulator::new(FIRMWARE_SEED);
+    sim.set_transfer_time(MicrosDurationU64::millis(10));
+    let num_leds = sim.led_strip().layout().num_leds();
+
+    shows[index].init(&mut sim, Params::new());
+    let mut clock = FrameClock::new(FRAME_RATE);
+    (0..FRAMES)
+        .map(|_| {
+            run_frame(shows[index], &mut sim, &mut clock);
+            (0..num_leds).map(|i| sim.led_strip().led(i).current()).collect()
+        })
+        .collect()
+}
+
+#[test]
+fn frames_of_every_show_survive_the_round_trip() {
+    let mut sent = 0;
+    let mut raw = 0;
+    for index in 0..SHOW_NUM {
+        let mut encoder = Encoder::new();
+        let mut device = Simulator::new(FIRMWARE_SEED);
+        for (number, frame) in frames(index).iter().enumerate() {
+            let sequence = encoder.sequence();
+            let packet = encoder.encode(frame);
+            sent += packet.len();
+            raw += 3 * frame.len();
+
+            // In pieces as small as USB packets
+            for piece in packet.chunks(64) {
+                device.send(piece);
+            }
+            assert_eq!(device.take_log(), [ACK, sequence], "show {index}, frame {number}");
+            let decoded = device.take_live_frame().unwrap();
+            assert!(decoded.colors() == frame.as_slice(), "show {index}, frame {number}");
+        }
+    }
+    assert!(sent * 4 < raw, "{sent} bytes sent for {raw} bytes of frames");
+}
+
+#[test]
+fn a_lost_packet_needs_a_key_frame() {
+    let frames = frames(0);
+    let mut encoder = Encoder::new();
+    let mut device = Simulator::new(FIRMWARE_SEED);
+
+    device.send(&encoder.encode(&frames[0]));
+    let mut broken = encoder.encode(&frames[1]);
+    broken[10] ^= 0x80;
+    device.send(&broken);
+    device.send(&encoder.encode(&frames[2]));
+    assert_eq!(device.take_log(), [ACK, 0, NAK, 1, NAK, 2]);
+
+    encoder.restart();
+    device.send(&encoder.encode(&frames[3]));
+    assert_eq!(device.take_log(), [ACK, 3]);
+    assert!(device.take_live_frame().unwrap().colors() == frames[3].as_slice());
+}
@@ -0,0 +1,103 @@
+//! Encoding of the LED colours for APA102 strips
+//!
+//! Every APA102 LED takes a header byte `0b111ggggg` with a 5 bit global
+//! brightness, followed by the 8 bit blue, green and red channels.  The
+//! intensity of a channel is `channel * global / (255 * 31)`.
+
+pub const MAX_GLOBAL: u8 = 31;
+const HEADER: u8 = 0b1110_0000;
+
+/// Full intensity of a 16 bit channel.
+const FULL: u32 = u16::MAX as u32;
+
+pub fn header(global: u8) -> u8 {
+    HEADER | global.min(MAX_GLOBAL)
+}
+
+/// Widens an 8 bit channel to 16 bit, `0xff` becomes `0xffff`.
+pub fn widen(channel: u8) -> u16 {
+    (channel as u16) << 8 | channel as u16
+}
+
+/// The 5 bit global brightness closest to an 8 bit master brightness.
+pub fn global_from_master(master: u8) -> u8 {
+    ((master as u16 * MAX_GLOBAL as u16 + 127) / 255) as u8
+}
+
+/// Encodes 16 bit channel intensities (red, green, blue) dimmed by `master`
+/// into the global brightness and the 8 bit channels.
+///
+/// Without HDR the master brightness goes into the global field and the
+/// channels are cut to 8 bit.  With HDR the global field is chosen per LED as
+/// low as possible, so that the 8 bit channels keep as much of the resolution
+/// as there is.  Dark colours then fade without banding.
+pub fn encode(rgb: [u16; 3], master: u8, hdr: bool) -> (u8, [u8; 3]) {
+    if !hdr {
+        return (global_from_master(master), rgb.map(|c| (c >> 8) as u8));
+    }
+
+    // The wanted channel * global, scaled by FULL
+    let target = rgb.map(|c| c as u32 * master as u32 * MAX_GLOBAL as u32);
+    let max = target.into_iter().max().unwrap_or(0);
+    let global = max.div_ceil(255 * FULL).clamp(1, MAX_GLOBAL as u32);
+    let step = global * FULL;
+    (global as u8, target.map(|t| ((t + step / 2) / step).min(255) as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn intensity(global: u8, channel: u8) -> f32 {
+        channel as f32 * global as f32 / (255.0 * 31.0)
+    }
+
+    #[test]
+    fn full_brightness_without_hdr_keeps_the_colour() {
+        assert_eq!(encode([widen(255), widen(16), widen(0)], 255, false), (31, [255, 16, 0]));
+        assert_eq!(header(31), 0xff);
+    }
+
+    #[test]
+    fn master_brightness_goes_into_the_global_field() {
+        assert_eq!(encode([widen(200), 0, 0], 128, false), (16, [200, 0, 0]));
+        assert_eq!(global_from_master(0), 0);
+        assert_eq!(global_from_master(255), 31);
+    }
+
+    #[test]
+    fn hdr_moves_dark_colours_into_the_global_field() {
+        assert_eq!(encode([0, 0, widen(16)], 255, true), (2, [0, 0, 248]));
+        assert_eq!(encode([widen(255), 0, 0], 255, true), (31, [255, 0, 0]));
+    }
+
+    #[test]
+    fn hdr_fades_dark_colours_in_more_steps() {
+        let levels = |hdr: bool| {
+            let mut levels: Vec<u32> = (0..=widen(16))
+                .map(|c| encode([c, 0, 0], 255, hdr))
+                .map(|(global, [r, _, _])| global as u32 * r as u32)
+                .collect();
+            levels.dedup();
+            levels.len()
+        };
+        assert_eq!(levels(false), 17);
+        assert!(levels(true) > 200);
+    }
+
+    proptest! {
+        #[test]
+        fn hdr_is_within_half_a_step(r: u16, g: u16, b: u16, master: u8) {
+            let rgb = [r, g, b];
+            let (global, channels) = encode(rgb, master, true);
+            prop_assert!((1..=MAX_GLOBAL).contains(&global));
+
+            for i in 0..3 {
+                let target = rgb[i] as f32 / 65535.0 * master as f32 / 255.0;
+                let error = (intensity(global, channels[i]) - target).abs();
+                prop_assert!(error <= 0.5 * global as f32 / (255.0 * 31.0) + 1e-6);
+            }
+        }
+    }
+}
@@ -1,12 +1,9 @@
 use embedded_hal::digital::v2::InputPin;
-use rp_pico::hal::{
-    gpio::{Pin, PinId, SioInput, FunctionSio, PullUp},
-    timer::Instant
-};
 
 use crate::conf::LONG_PRESS_TIME;
+use crate::hardware::Instant;
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum ButtonState {
     Up,
     Down,
@@ -14,14 +11,14 @@ pub enum ButtonState {
     LongPressed
 }
 
-pub struct Button<P: PinId> {
-    pin: Pin<P, FunctionSio<SioInput>, PullUp>,
+pub struct Button<P: InputPin> {
+    pin: P,
     press_time: Option<Instant>,
     state: ButtonState,
 }
 
-impl<P: PinId> Button<P> {
-    pub fn new(pin: Pin<P, FunctionSio<SioInput>, PullUp>) -> Button<P> {
+impl<P: InputPin> Button<P> {
+    pub fn new(pin: P) -> Button<P> {
         Button {
             pin,
             press_time: None,
@@ -35,7 +32,7 @@ impl<P: PinId> Button<P> {
     }
 
     fn determine_state(&mut self, current_time: Instant) -> ButtonState {
-        let (press_time, state) = if self.pin.is_low().unwrap() {
+        let (press_time, state) = if self.pin.is_low().unwrap_or(false) {
             self.press_time.map_or_else(
                 || (
                     if self.state == ButtonState::Up {
@@ -0,0 +1,243 @@
+//! Remote control over the USB serial port
+//!
+//! The host sends one command per line, ended by `\n` or `\r`:
+//!
+//! ```text
+//! next | prev | select <show> | auto on|off | brightness <0-255> | status | list | opc
+//! ```
+//!
+//! Every command is answered with a line `ok` or `error: ...`.  `status`
+//! answers with the state instead of `ok`, `list` sends the names of the shows
+//! before its `ok`.  After `opc` the port takes Open Pixel Control messages
+//! until the host closes it, see `opc`.  The show runner carries the commands
+//! out, see `show::run_show`.
+
+use core::fmt;
+
+use crate::text::{Line, Text};
+
+/// Received bytes that are not yet read as lines.
+const READ_BUFFER: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command<'a> {
+    Next,
+    /// Back to the entry of the playlist played before.
+    Prev,
+    Select(&'a str),
+    /// Turns the automatic change of the shows on or off.
+    Auto(bool),
+    Brightness(u8),
+    Status,
+    List,
+    /// Switches the port to Open Pixel Control, which `StreamDecoder` does.
+    Opc
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParseError<'a> {
+    NotText,
+    Unknown(&'a str),
+    /// The command name and what is wrong with its argument.
+    Argument(&'a str, &'static str)
+}
+
+impl fmt::Display for ParseError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::NotText => write!(f, "not text"),
+            ParseError::Unknown(name) => write!(f, "unknown command {name}"),
+            ParseError::Argument(name, expected) => write!(f, "{name} expects {expected}")
+        }
+    }
+}
+
+impl Command<'_> {
+    pub fn parse(line: &[u8]) -> Result<Command<'_>, ParseError<'_>> {
+        let line = core::str::from_utf8(line).map_err(|_| ParseError::NotText)?.trim();
+        let (name, argument) = match line.split_once(' ') {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (line, "")
+        };
+        let none = |command| if argument.is_empty() { Ok(command) } else { Err(ParseError::Argument(name, "nothing")) };
+        match name {
+            "next" => none(Command::Next),
+            "prev" => none(Command::Prev),
+            "status" => none(Command::Status),
+            "list" => none(Command::List),
+            "opc" => none(Command::Opc),
+            "select" if argument.is_empty() => Err(ParseError::Argument(name, "a show")),
+            "select" => Ok(Command::Select(argument)),
+            "auto" => match argument {
+                "on" => Ok(Command::Auto(true)),
+                "off" => Ok(Command::Auto(false)),
+                _ => Err(ParseError::Argument(name, "on or off"))
+            },
+            "brightness" => argument
+                .parse()
+                .map(Command::Brightness)
+                .map_err(|_| ParseError::Argument(name, "0 to 255")),
+            _ => Err(ParseError::Unknown(name))
+        }
+    }
+}
+
+/// Lines were lost, they came in faster than they were read.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Overflow;
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "input overflow, lines lost")
+    }
+}
+
+/// Splits the bytes received into lines, they may come in any pieces.
+pub struct LineReader {
+    buffer: Text<READ_BUFFER>,
+    /// Whether the bytes up to the next line end are dropped.
+    skipping: bool,
+    /// Where in the buffer lines were lost.
+    lost: Option<usize>
+}
+
+impl LineReader {
+    pub const fn new() -> LineReader {
+        LineReader { buffer: Text::new(), skipping: false, lost: None }
+    }
+
+    /// Adds received bytes.  If they don't fit, see `lose`.
+    pub fn push(&mut self, bytes: &[u8]) {
+        let mut bytes = bytes;
+        if self.skipping {
+            let Some(end) = bytes.iter().position(|&b| is_end(b)) else {
+                return;
+            };
+            self.skipping = false;
+            bytes = &bytes[end + 1..];
+        }
+        if !self.buffer.push(bytes) {
+            self.lose();
+        }
+    }
+
+    /// Tells that bytes after those pushed were lost.  The line they cut is
+    /// dropped up to its end.  If there were complete lines before, reading
+    /// gets to `Overflow` after them, otherwise the buffer held no line anyway.
+    pub fn lose(&mut self) {
+        let kept = self.buffer.as_bytes().iter().rposition(|&b| is_end(b)).map_or(0, |end| end + 1);
+        self.buffer.truncate(kept);
+        self.skipping = true;
+        if kept > 0 && self.lost.is_none() {
+            self.lost = Some(kept);
+        }
+    }
+
+    /// The next complete line that is not empty, without its end, or
+    /// `Overflow` where lines were lost.  Lines too long for a `Line` are cut
+    /// off.
+    pub fn read_line(&mut self) -> Option<Result<Line, Overflow>> {
+        loop {
+            if self.lost == Some(0) {
+                self.lost = None;
+                return Some(Err(Overflow));
+            }
+            let end = self.next_end()?;
+            let mut line = Line::new();
+            line.push(&self.buffer.as_bytes()[..end]);
+            self.buffer.consume(end + 1);
+            self.lost = self.lost.map(|lost| lost - (end + 1));
+            if !line.is_empty() {
+                return Some(Ok(line));
+            }
+        }
+    }
+
+    fn next_end(&self) -> Option<usize> {
+        self.buffer.as_bytes().iter().position(|&b| is_end(b))
+    }
+}
+
+fn is_end(byte: u8) -> bool {
+    byte == b'\n' || byte == b'\r'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn commands_parse() {
+        assert_eq!(Command::parse(b"next"), Ok(Command::Next));
+        assert_eq!(Command::parse(b" prev "), Ok(Command::Prev));
+        assert_eq!(Command::parse(b"select  fire_red"), Ok(Command::Select("fire_red")));
+        assert_eq!(Command::parse(b"auto off"), Ok(Command::Auto(false)));
+        assert_eq!(Command::parse(b"brightness 128"), Ok(Command::Brightness(128)));
+        assert_eq!(Command::parse(b"status"), Ok(Command::Status));
+        assert_eq!(Command::parse(b"list"), Ok(Command::List));
+        assert_eq!(Command::parse(b"opc"), Ok(Command::Opc));
+    }
+
+    #[test]
+    fn bad_commands_are_refused() {
+        assert_eq!(Command::parse(b"jump"), Err(ParseError::Unknown("jump")));
+        assert_eq!(Command::parse(b"next 3"), Err(ParseError::Argument("next", "nothing")));
+        assert_eq!(Command::parse(b"select"), Err(ParseError::Argument("select", "a show")));
+        assert_eq!(Command::parse(b"auto maybe"), Err(ParseError::Argument("auto", "on or off")));
+        assert_eq!(Command::parse(b"brightness 256"), Err(ParseError::Argument("brightness", "0 to 255")));
+        assert_eq!(Command::parse(&[0xff]), Err(ParseError::NotText));
+    }
+
+    #[test]
+    fn lines_end_with_either_line_end() {
+        let mut reader = LineReader::new();
+        reader.push(b"next\r\nsta");
+        assert_eq!(reader.read_line().unwrap().unwrap().as_bytes(), b"next");
+        assert!(reader.read_line().is_none());
+        reader.push(b"tus\n\n");
+        assert_eq!(reader.read_line().unwrap().unwrap().as_bytes(), b"status");
+        assert!(reader.read_line().is_none());
+    }
+
+    #[test]
+    fn garbage_without_line_end_is_dropped() {
+        let mut reader = LineReader::new();
+        reader.push(&[b'x'; READ_BUFFER + 1]);
+        reader.push(b"\nlist\n");
+        assert_eq!(reader.read_line().unwrap().unwrap().as_bytes(), b"list");
+    }
+
+    #[test]
+    fn lines_lost_in_a_burst_are_answered() {
+        let mut reader = LineReader::new();
+        reader.push(b"next\n");
+        reader.push(&b"status\n".repeat(40));
+        reader.push(b"us\nlist\n");
+        assert_eq!(reader.read_line().unwrap().unwrap().as_bytes(), b"next");
+        for _ in 0..35 {
+            assert_eq!(reader.read_line().unwrap().unwrap().as_bytes(), b"status");
+        }
+        assert!(matches!(reader.read_line(), Some(Err(Overflow))));
+        assert_eq!(reader.read_line().unwrap().unwrap().as_bytes(), b"list");
+        assert!(reader.read_line().is_none());
+    }
+
+    proptest! {
+        #[test]
+        fn lines_survive_any_split(split in 0usize..28) {
+            let stream = b"select snow\nauto on\rstatus\n";
+            let mut reader = LineReader::new();
+            reader.push(&stream[..split]);
+            let mut lines = Vec::new();
+            while let Some(Ok(line)) = reader.read_line() {
+                lines.push(line.as_bytes().to_vec());
+            }
+            reader.push(&stream[split..]);
+            while let Some(Ok(line)) = reader.read_line() {
+                lines.push(line.as_bytes().to_vec());
+            }
+            prop_assert_eq!(lines, [b"select snow".to_vec(), b"auto on".to_vec(), b"status".to_vec()]);
+        }
+    }
+}
@@ -1,10 +1,100 @@
 use fugit::MicrosDurationU64;
 
-pub const STRIP_LENGTH: usize = 60;
-pub const STRIP_NUM: usize = 24;
-pub const NUM_LED: usize = STRIP_LENGTH * STRIP_NUM;
+use crate::protocol::{ChannelOrder, Protocol};
+use crate::ws2812;
+use crate::led::Color;
+use crate::playlist::{Entry, Order, Playlist};
+use crate::transition::{Blend, Transition};
+
+/// Capacity of the LED buffers, the `layout::Layout` in use has to fit in.
+pub const MAX_STRIPS: usize = 24;
+pub const MAX_LEDS: usize = 1440;
+pub const MAX_DEAD_LEDS: usize = 16;
+pub const MAX_FEEDS: usize = 8;
+pub const MAX_PLAYLIST: usize = 32;
+pub const MAX_NO_REPEAT: usize = 8;
+/// How long a show runs unless its playlist entry says otherwise.
 pub const AUTO_SHOW_DELAY: MicrosDurationU64 = MicrosDurationU64::secs(60);
+/// How a show takes over from the one before unless its playlist entry says
+/// otherwise, `Transition::cut()` for a hard cut.
+pub const TRANSITION: Transition = Transition::new(Blend::Crossfade, MicrosDurationU64::millis(1_500));
+/// How long the frames streamed by a host stay before the playlist goes on.
+pub const LIVE_TIMEOUT: MicrosDurationU64 = MicrosDurationU64::secs(5);
+/// The frames per second the shows are paced to.  The single wire LEDs take
+/// longer to shift out a chain of 720, 21.9 ms for the WS2812 and 29.1 ms for
+/// the SK6812, shows that move one step per frame run slower with them.
+#[cfg(not(feature = "ws2812"))]
+pub const FRAME_RATE: u32 = 50;
+#[cfg(all(feature = "ws2812", not(feature = "sk6812")))]
+pub const FRAME_RATE: u32 = 40;
+#[cfg(feature = "sk6812")]
+pub const FRAME_RATE: u32 = 30;
+/// The shows of the installation, by the names of the `programme::Programme`.
+/// E.g. `Playlist::new(...).with_order(Order::Shuffled).without_repeats(3)`,
+/// `Entry::new("snake").until_finished()`,
+/// `Entry::new("eu_stars").with_colors(BLACK, RED)`.
+pub const PLAYLIST: Playlist = Playlist::new(&[
+    Entry::new("snow"),
+    Entry::new("sine"),
+    Entry::new("particles_spiral"),
+    Entry::new("planets"),
+    Entry::new("fire_green").with_transition(Transition::new(Blend::WipeUp, MicrosDurationU64::secs(2))),
+    Entry::new("spiral_lift")
+        .with_duration(MicrosDurationU64::secs(30))
+        .with_transition(Transition::new(Blend::SpiralWipe, MicrosDurationU64::secs(2))),
+    Entry::new("heavy_snow"),
+    Entry::new("sea_wave_chaos"),
+    Entry::new("fireworks")
+        .with_duration(MicrosDurationU64::secs(120))
+        .with_transition(Transition::new(Blend::FadeThroughBlack, MicrosDurationU64::secs(2))),
+    Entry::new("spiral_swirl")
+        .with_duration(MicrosDurationU64::secs(30))
+        .with_transition(Transition::new(Blend::SpiralWipe, MicrosDurationU64::secs(2))),
+    Entry::new("snow_sparks"),
+    Entry::new("eo_stars"),
+    Entry::new("particles"),
+    Entry::new("sea_wave_order"),
+    Entry::new("snake"),
+    Entry::new("spark_fall"),
+    Entry::new("eu_stars"),
+    Entry::new("fire_red").with_transition(Transition::new(Blend::WipeUp, MicrosDurationU64::secs(2))),
+]).with_order(Order::Sequential);
 pub const SNAKE_PROB: u8 = 32;
 pub const LONG_PRESS_TIME: MicrosDurationU64 = MicrosDurationU64::millis(1_000);
 pub const SPARK_PROB: f32 = 1e-2;
 pub const SPARKS_PER_STRIP: usize = 8;
+/// The LED chips of the strips, chosen by the `ws2812` and `sk6812` features.
+#[cfg(not(feature = "ws2812"))]
+pub const PROTOCOL: Protocol = Protocol::Apa102;
+#[cfg(all(feature = "ws2812", not(feature = "sk6812")))]
+pub const PROTOCOL: Protocol = Protocol::Ws2812;
+#[cfg(feature = "sk6812")]
+pub const PROTOCOL: Protocol = Protocol::Sk6812Rgbw;
+// A chain of single wire LEDs has to be through within a frame
+const _: () = assert!(
+    !matches!(PROTOCOL, Protocol::Ws2812 | Protocol::Sk6812Rgbw)
+        || ws2812::frame_us(MAX_LEDS / 2, matches!(PROTOCOL, Protocol::Sk6812Rgbw)) <= 1_000_000 / FRAME_RATE,
+    "FRAME_RATE is too high for the chains"
+);
+/// Channel order of the strips on the two chains.
+#[cfg(not(feature = "ws2812"))]
+pub const CHANNEL_ORDER: [ChannelOrder; 2] = [ChannelOrder::Bgr, ChannelOrder::Bgr];
+#[cfg(feature = "ws2812")]
+pub const CHANNEL_ORDER: [ChannelOrder; 2] = [ChannelOrder::Grb, ChannelOrder::Grb];
+/// Master brightness, sent in the APA102 global brightness field.
+pub const BRIGHTNESS: u8 = 255;
+/// Spread dark colours over the global brightness and the channels.
+pub const HDR: bool = false;
+/// Gamma of the output stage, 1.0 sends the colours of the shows linearly.
+pub const GAMMA: f32 = 2.2;
+/// What full white looks like on the strips, FastLED's value for 5050 LEDs.
+pub const WHITE_BALANCE: Color = Color { r: 255, g: 176, b: 240 };
+/// Colour temperature of white in kelvin, 6600 is neutral.
+pub const TEMPERATURE: u32 = 6600;
+/// Current budget of each power feed, the chain is split evenly between them.
+pub const FEED_CURRENT_MA: u32 = 4_000;
+pub const POWER_FEEDS: usize = 2;
+/// Current of an APA102 channel at full intensity and of a LED that is off.
+pub const CHANNEL_CURRENT_MA: [u32; 3] = [20, 20, 20];
+pub const IDLE_CURRENT_MA: u32 = 1;
+pub const SPI_BAUD_RATE: u32 = 8_000_000u32;
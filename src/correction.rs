@@ -0,0 +1,135 @@
+//! Colour correction of the output stage
+//!
+//! The shows think in perceived brightness, the LEDs are linear.  Every 8 bit
+//! channel goes through a gamma table into 16 bit intensity and is then scaled
+//! per channel for the white balance of the strips and the colour temperature.
+
+use libm::{logf, powf};
+
+use crate::led::Color;
+
+/// Scale factor of 1.0 for the channel scales.
+const UNITY: u32 = 1 << 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Correction {
+    lut: [u16; 256],
+    white_balance: [u8; 3],
+    temperature: [u8; 3],
+    scale: [u32; 3]
+}
+
+impl Correction {
+    /// No correction at all, the channels are sent as they are.
+    pub fn linear() -> Correction {
+        Correction::new(1.0)
+    }
+
+    /// Gamma correction only, `gamma` is typically 2.2 to 2.8.
+    pub fn new(gamma: f32) -> Correction {
+        let mut lut = [0u16; 256];
+        for (i, value) in lut.iter_mut().enumerate() {
+            *value = (powf(i as f32 / 255.0, gamma) * u16::MAX as f32 + 0.5) as u16;
+        }
+        Correction { lut, white_balance: [255; 3], temperature: [255; 3], scale: [UNITY; 3] }
+    }
+
+    /// The colour the strips show for full white, measured or looked up for
+    /// the LED type.  The channels are dimmed to get a neutral white.
+    pub fn with_white_balance(mut self, color: Color) -> Correction {
+        self.white_balance = [color.r, color.g, color.b];
+        self.update_scale();
+        self
+    }
+
+    /// Tints white to the colour of a black body of `kelvin`, 6600 is neutral.
+    pub fn with_temperature(mut self, kelvin: u32) -> Correction {
+        self.temperature = temperature_rgb(kelvin);
+        self.update_scale();
+        self
+    }
+
+    fn update_scale(&mut self) {
+        for i in 0..3 {
+            let factor = self.white_balance[i] as u32 * self.temperature[i] as u32;
+            // 255 * 255 becomes exactly UNITY
+            self.scale[i] = (factor * UNITY + 255 * 255 / 2) / (255 * 255);
+        }
+    }
+
+    /// The 16 bit intensities (red, green, blue) of a colour.
+    pub fn apply(&self, color: Color) -> [u16; 3] {
+        let rgb = [color.r, color.g, color.b];
+        [0, 1, 2].map(|i| ((self.lut[rgb[i] as usize] as u32 * self.scale[i]) >> 16) as u16)
+    }
+}
+
+/// The colour of a black body, after Tanner Helland's fit of the CIE tables.
+fn temperature_rgb(kelvin: u32) -> [u8; 3] {
+    let t = kelvin.clamp(1000, 40000) as f32 / 100.0;
+    let clamp = |value: f32| value.clamp(0.0, 255.0) as u8;
+
+    let red = if t <= 66.0 { 255.0 } else { 329.69873 * powf(t - 60.0, -0.13320476) };
+    let green = if t <= 66.0 {
+        99.4708 * logf(t) - 161.11957
+    } else {
+        288.12216 * powf(t - 60.0, -0.07551485)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.51773 * logf(t - 10.0) - 305.0448
+    };
+    [clamp(red), clamp(green), clamp(blue)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apa102::widen;
+    use crate::led::WHITE;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn linear_changes_nothing(r: u8, g: u8, b: u8) {
+            let corrected = Correction::linear().apply(Color { r, g, b });
+            prop_assert_eq!(corrected, [widen(r), widen(g), widen(b)]);
+        }
+
+        #[test]
+        fn gamma_is_monotonic(value in 0u8..255, gamma in 1.0f32..3.0) {
+            let correction = Correction::new(gamma);
+            let dark = correction.apply(Color { r: value, g: value, b: value });
+            let bright = correction.apply(Color { r: value + 1, g: value + 1, b: value + 1 });
+            prop_assert!(dark[0] <= bright[0]);
+        }
+    }
+
+    #[test]
+    fn gamma_darkens_the_low_end() {
+        let correction = Correction::new(2.2);
+        assert_eq!(correction.apply(WHITE), [u16::MAX; 3]);
+        assert_eq!(correction.apply(Color { r: 0, g: 0, b: 0 }), [0; 3]);
+        let [half, _, _] = correction.apply(Color { r: 128, g: 0, b: 0 });
+        assert!((14000..15000).contains(&half));
+    }
+
+    #[test]
+    fn white_balance_scales_the_channels() {
+        let correction = Correction::linear().with_white_balance(Color { r: 255, g: 176, b: 240 });
+        assert_eq!(correction.apply(WHITE), [65535, 45232, 61680]);
+    }
+
+    #[test]
+    fn temperatures() {
+        assert_eq!(temperature_rgb(6600), [255, 255, 255]);
+        let [r, g, b] = temperature_rgb(2700);
+        assert!(r == 255 && g < 200 && b < 150);
+        let [r, _, b] = temperature_rgb(10000);
+        assert!(r < 220 && b == 255);
+        assert_eq!(Correction::linear().with_temperature(6600), Correction::linear());
+    }
+}
@@ -0,0 +1,60 @@
+//! A chain of LEDs fed by DMA from two buffers
+
+use rp_pico::hal::dma::{
+    single_buffer::{Config, Transfer},
+    ReadTarget, SingleChannel, WriteTarget,
+};
+
+/// The data of the next frame goes to the back buffer while the front buffer
+/// is being sent.  `send()` waits for the front buffer to be through and
+/// swaps them.
+pub struct DmaChain<CH, TO, W: 'static>
+where
+    CH: SingleChannel,
+    TO: WriteTarget<TransmittedWord = W>,
+    &'static mut [W]: ReadTarget<ReceivedWord = W>,
+{
+    back: Option<&'static mut [W]>,
+    state: Option<State<CH, TO, W>>,
+}
+
+enum State<CH, TO, W: 'static>
+where
+    CH: SingleChannel,
+    TO: WriteTarget<TransmittedWord = W>,
+    &'static mut [W]: ReadTarget<ReceivedWord = W>,
+{
+    Idle(CH, TO, &'static mut [W]),
+    Sending(Transfer<CH, &'static mut [W], TO>),
+}
+
+impl<CH, TO, W> DmaChain<CH, TO, W>
+where
+    CH: SingleChannel,
+    TO: WriteTarget<TransmittedWord = W>,
+    &'static mut [W]: ReadTarget<ReceivedWord = W>,
+{
+    /// The buffers have the length of a frame, all of them is sent.
+    pub fn new(ch: CH, to: TO, front: &'static mut [W], back: &'static mut [W]) -> Self {
+        assert_eq!(front.len(), back.len());
+        DmaChain { back: Some(back), state: Some(State::Idle(ch, to, front)) }
+    }
+
+    /// The buffer for the next frame.
+    pub fn back(&mut self) -> &mut [W] {
+        self.back.as_deref_mut().unwrap()
+    }
+
+    /// Starts sending the back buffer as soon as the front buffer is sent.
+    pub fn send(&mut self) {
+        let (ch, to, front) = match self.state.take().unwrap() {
+            State::Idle(ch, to, front) => (ch, to, front),
+            State::Sending(transfer) => {
+                let (ch, front, to) = transfer.wait();
+                (ch, to, front)
+            }
+        };
+        let back = self.back.replace(front).unwrap();
+        self.state = Some(State::Sending(Config::new(ch, back, to).start()));
+    }
+}
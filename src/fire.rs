@@ -1,4 +1,4 @@
-use crate::{ledstrip::LEDStrip, conf::NUM_LED, conf::STRIP_LENGTH, conf::STRIP_NUM, random::Random, math8::{qsub8, scale8, qadd8}, led::Color, interface::Interface};
+use crate::{ledstrip::LEDStrip, conf::MAX_LEDS, random::Random, math8::{qsub8, scale8, qadd8}, led::Color, frameclock::Delta, hardware::Hardware, show::Show};
 
 const COOLING: u8 = 8;
 const SPARK_PROB: u8 = 10;
@@ -11,7 +11,7 @@ pub enum FireColor {
 
 
 pub struct Fire {
-    heat: [u8; NUM_LED],
+    heat: [u8; MAX_LEDS],
     rng: Random,
     color: FireColor
 }
@@ -20,35 +20,36 @@ pub struct Fire {
 
 impl Fire {
     pub fn new_red() -> Fire {
-        Fire { heat: [0u8; NUM_LED], rng: Random::new(23124923), color: FireColor::Red }
+        Fire { heat: [0u8; MAX_LEDS], rng: Random::new(23124923), color: FireColor::Red }
     }
     pub fn new_green() -> Fire {
-        Fire { heat: [0u8; NUM_LED], rng: Random::new(23124923), color: FireColor::Green }
+        Fire { heat: [0u8; MAX_LEDS], rng: Random::new(23124923), color: FireColor::Green }
     }
 
     pub fn process(&mut self, led_strip: &mut LEDStrip) {
+        let layout = led_strip.layout();
 
-        let spark_prob = SPARK_PROB * STRIP_NUM as u8;
+        let spark_prob = SPARK_PROB.saturating_mul(layout.strips() as u8);
 
-        for h in self.heat.iter_mut() {
+        for h in self.heat[..layout.num_leds()].iter_mut() {
             *h = qsub8(*h, scale8(self.rng.value8(), COOLING))
         }
 
-        for y in (3..STRIP_LENGTH).rev() {
-            for s in 0..STRIP_NUM {
-                let i = s*STRIP_LENGTH + y;
+        for y in (3..layout.length()).rev() {
+            for s in 0..layout.strips() {
+                let i = layout.index(s, y);
                 self.heat[i] = ((self.heat[i-1] as u16 + self.heat[i-2] as u16 + self.heat[i-3] as u16) / 3) as u8;
             }
         }
 
         if self.rng.value8() < spark_prob {
-            let stripe = self.rng.value32(STRIP_NUM as u32) as usize;
-            let y = self.rng.value32(7) as usize;
-            let i = stripe*STRIP_LENGTH + y;
+            let stripe = self.rng.value32(layout.strips() as u32) as usize;
+            let y = self.rng.value32(7.min(layout.length() as u32)) as usize;
+            let i = layout.index(stripe, y);
             self.heat[i] = qadd8(self.heat[i], self.rng.value8().max(166));
         }
 
-        for i in 0..NUM_LED {
+        for i in 0..layout.num_leds() {
             let temperature = self.heat[i];
             let color = match self.color {
                 FireColor::Red => self.tempeature_to_red_color(temperature),
@@ -83,17 +84,17 @@ impl Fire {
         }
         Color { r: 0, g: heatramp, b: 0 }
     }
+}
 
-
-    pub fn show(&mut self, interface: &mut Interface) {
-        loop {
-            self.process(&mut interface.led_strip());
-            interface.write_spi();
-
-            if interface.do_next() {
-                interface.led_strip().black();
-                break;
-            }
+impl<H: Hardware> Show<H> for Fire {
+    fn name(&self) -> &'static str {
+        match self.color {
+            FireColor::Red => "fire_red",
+            FireColor::Green => "fire_green"
         }
     }
+
+    fn frame(&mut self, interface: &mut H, _dt: Delta) {
+        self.process(interface.led_strip());
+    }
 }
@@ -0,0 +1,141 @@
+//! Pacing the frames to a fixed frame rate
+//!
+//! The shows move one step per frame at the target frame rate.  If a frame
+//! takes longer, the next one gets a larger `Delta` so the shows keep up with
+//! real time.
+
+use fugit::MicrosDurationU64;
+use libm::powf;
+
+use crate::hardware::Instant;
+
+/// A frame never covers more than that many frames, so a long stall doesn't
+/// throw the sparks and planets out of the picture.
+const MAX_FRAMES: u32 = 4;
+
+/// The time a frame covers, in 1/256 frames of the target frame rate.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Delta(u32);
+
+impl Delta {
+    pub const ONE: Delta = Delta(256);
+
+    /// The delta of `elapsed` for frames of `period`.
+    pub fn new(elapsed: MicrosDurationU64, period: MicrosDurationU64) -> Delta {
+        let fraction = elapsed.to_micros() * 256 / period.to_micros().max(1);
+        Delta(fraction.min(MAX_FRAMES as u64 * 256) as u32)
+    }
+
+    pub fn frames(self) -> f32 {
+        self.0 as f32 / 256.0
+    }
+
+    /// Scales a per frame step of fixed point physics.
+    pub fn scale(self, step: isize) -> isize {
+        (step * self.0 as isize) >> 8
+    }
+
+    /// The part of the way to a target covered in this delta, if `rate` of it
+    /// is covered in a frame.
+    pub fn rate(self, rate: f32) -> f32 {
+        if self == Delta::ONE {
+            return rate;
+        }
+        1.0 - powf(1.0 - rate, self.frames())
+    }
+
+    /// `rate()` for the rates of `led::decay()`, in 1/256.
+    pub fn decay(self, rate: u8) -> u8 {
+        if self == Delta::ONE {
+            return rate;
+        }
+        (self.rate(rate as f32 / 256.0) * 256.0 + 0.5).min(255.0) as u8
+    }
+}
+
+/// Keeps track of when the frames start.
+pub struct FrameClock {
+    period: MicrosDurationU64,
+    last: Option<Instant>
+}
+
+impl FrameClock {
+    pub fn new(fps: u32) -> FrameClock {
+        FrameClock { period: MicrosDurationU64::micros(1_000_000 / fps as u64), last: None }
+    }
+
+    pub fn period(&self) -> MicrosDurationU64 {
+        self.period
+    }
+
+    /// Starts a frame at `now`, returns the time since the start of the last
+    /// one.  The first frame covers one frame.
+    pub fn tick(&mut self, now: Instant) -> Delta {
+        let delta = match self.last {
+            Some(last) => Delta::new(now.checked_duration_since(last).unwrap_or(self.period), self.period),
+            None => Delta::ONE
+        };
+        self.last = Some(now);
+        delta
+    }
+
+    /// When the next frame is due.
+    pub fn next_frame(&self) -> Instant {
+        self.last.map_or(Instant::from_ticks(0), |last| last + self.period)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn at(ms: u64) -> Instant {
+        Instant::from_ticks(ms * 1000)
+    }
+
+    #[test]
+    fn frames_on_time_cover_one_frame() {
+        let mut clock = FrameClock::new(50);
+        assert_eq!(clock.tick(at(100)), Delta::ONE);
+        assert_eq!(clock.next_frame(), at(120));
+        assert_eq!(clock.tick(at(120)), Delta::ONE);
+        assert_eq!(clock.tick(at(140)), Delta::ONE);
+    }
+
+    #[test]
+    fn late_frames_cover_more() {
+        let mut clock = FrameClock::new(50);
+        clock.tick(at(0));
+        let delta = clock.tick(at(30));
+        assert_eq!(delta.frames(), 1.5);
+        assert_eq!(delta.scale(-24), -36);
+        assert_eq!(clock.next_frame(), at(50));
+    }
+
+    #[test]
+    fn stalls_are_capped() {
+        let mut clock = FrameClock::new(50);
+        clock.tick(at(0));
+        assert_eq!(clock.tick(at(5000)).frames(), MAX_FRAMES as f32);
+    }
+
+    #[test]
+    fn rates_add_up_over_the_frames() {
+        let half = Delta::new(MicrosDurationU64::millis(10), MicrosDurationU64::millis(20));
+        let two = Delta::new(MicrosDurationU64::millis(40), MicrosDurationU64::millis(20));
+        let left = (1.0 - half.rate(0.1)) * (1.0 - half.rate(0.1));
+        assert!((1.0 - left - 0.1).abs() < 1e-6);
+        assert!((two.rate(0.1) - 0.19).abs() < 1e-6);
+        assert_eq!(Delta::ONE.decay(77), 77);
+        assert_eq!(two.decay(128), 192);
+        assert_eq!(two.decay(255), 255);
+    }
+
+    proptest! {
+        #[test]
+        fn one_frame_scales_nothing(step in -100_000isize..100_000) {
+            prop_assert_eq!(Delta::ONE.scale(step), step);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use fugit::TimerInstantU64;
+
+use crate::button::ButtonState;
+use crate::command::Overflow;
+use crate::ledstrip::LEDStrip;
+use crate::random::Random;
+use crate::text::Line;
+
+pub type Instant = TimerInstantU64<1_000_000>;
+
+/// What a show can see of the installation it is running on.
+///
+/// `renderer::Renderer` implements this on core 1 of the RP2040.  Shows only
+/// talk to this trait, so the same show code can run against a mock or a
+/// desktop backend.
+pub trait Hardware {
+    fn get_time(&self) -> Instant;
+
+    fn random(&mut self) -> &mut Random;
+
+    fn led_strip(&mut self) -> &mut LEDStrip;
+
+    /// Sends the current content of the LED strip out to the LEDs.
+    fn write_frame(&mut self);
+
+    /// Whether the first button asks for the next show.
+    fn do_next(&mut self) -> bool;
+
+    /// Whether the shows change on their own, toggled by a long press of the
+    /// first button.
+    fn auto_show(&mut self) -> bool;
+
+    fn set_auto_show(&mut self, on: bool);
+
+    /// State of the second button, the one that the shows may react to.
+    fn button_state(&mut self) -> ButtonState;
+
+    fn led_on(&mut self);
+
+    fn led_off(&mut self);
+
+    fn delay_ms(&mut self, delay: u32);
+
+    /// Waits until `time`, returns at once if it has passed.
+    fn wait_until(&mut self, time: Instant);
+
+    /// Text for the USB serial port.
+    fn log(&mut self, message: &[u8]);
+
+    /// The next command line received over USB, or `Overflow` where lines
+    /// were lost, see `command`.
+    fn read_line(&mut self) -> Option<Result<Line, Overflow>>;
+
+    /// Whether the host streamed a frame that is not yet on the LED strip,
+    /// see `stream`.
+    fn has_live_frame(&self) -> bool;
+
+    /// Puts the latest frame streamed by the host onto the LED strip, returns
+    /// whether a new one came since the last call.
+    fn write_live_frame(&mut self) -> bool;
+}
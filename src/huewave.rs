@@ -1,6 +1,4 @@
-use crate::{ledstrip::LEDStrip, conf::STRIP_LENGTH, conf::STRIP_NUM, led::Color};
-
-const HUE_STEP: f32 = 1.0 / STRIP_LENGTH as f32;
+use crate::{ledstrip::LEDStrip, led::Color};
 
 pub struct HueWave {
     step: usize
@@ -12,15 +10,17 @@ impl HueWave {
     }
 
     pub fn process(&mut self, led_strip: &mut LEDStrip) {
-        let mut hue = self.step as f32 / STRIP_LENGTH as f32;
-        for y in 0..STRIP_LENGTH {
-            for x in 0..STRIP_NUM {
-                let pos = (x * STRIP_LENGTH + y) as isize;
+        let layout = led_strip.layout();
+        let hue_step = 1.0 / layout.length() as f32;
+        let mut hue = self.step as f32 / layout.length() as f32;
+        for y in 0..layout.length() {
+            for x in 0..layout.strips() {
+                let pos = layout.index(x, y) as isize;
                 led_strip.set_led(pos, Color::from_hsv(hue, 1.0, 0.03));
             }
-            hue = (hue + HUE_STEP) % 1.0;
+            hue = (hue + hue_step) % 1.0;
         }
-        self.step = (self.step + 1) % STRIP_LENGTH;
+        self.step = (self.step + 1) % layout.length();
     }
 
 }
@@ -1,52 +1,69 @@
+use core::sync::atomic::{compiler_fence, Ordering};
+
 use rp_pico::hal::{
-    clocks::{init_clocks_and_plls, Clock},
+    clocks::init_clocks_and_plls,
+    dma::DMAExt,
+    multicore::{Multicore, Stack},
     pac,
-    sio::Sio,
-    spi::{Spi, Enabled},
+    sio::{Sio, SioFifo},
     gpio::{
         self,
         Pin,
         Pins,
+        FunctionSioInput,
         FunctionSioOutput,
-        FunctionSpi,
         PullDown,
+        PullUp,
     },
     Timer,
-    timer::Instant,
     watchdog::Watchdog,
     usb::UsbBus,
 };
 
-use embedded_hal::{
-    spi::MODE_0, digital::v2::OutputPin,
-    blocking::spi::Write
-};
-use fugit::RateExtU32;
+#[cfg(feature = "ws2812")]
+use rp_pico::hal::clocks::Clock;
+
+use embedded_hal::digital::v2::OutputPin;
 use usb_device::{prelude::*, bus::UsbBusAllocator};
 use usbd_serial::SerialPort;
 
-use crate::ledstrip::LEDStrip;
-use crate::showtimer::ShowTimer;
-use crate::button::{Button, ButtonState};
-use crate::random::Random;
-
-type ButtonPin1 = gpio::bank0::Gpio21;
-type ButtonPin2 = gpio::bank0::Gpio20;
-type LedPin1 = gpio::bank0::Gpio10;
-type LedPin2 = gpio::bank0::Gpio11;
-
-type SCLK0 = Pin<gpio::bank0::Gpio6, FunctionSpi, PullDown>;
-type MOSI0 = Pin<gpio::bank0::Gpio7, FunctionSpi, PullDown>;
-
-type Spi0Pinout = (MOSI0, SCLK0);
-
-type SCLK1 = Pin<gpio::bank0::Gpio14, FunctionSpi, PullDown>;
-type MOSI1 = Pin<gpio::bank0::Gpio15, FunctionSpi, PullDown>;
-
-type Spi1Pinout = (MOSI1, SCLK1);
-
-const PERI_FEQUENCY: u32 = 450_000_000u32;
-const BAUD_RATE:  u32 = 8_000_000u32;
+use lumio::conf::{
+    BRIGHTNESS, CHANNEL_CURRENT_MA, CHANNEL_ORDER, FEED_CURRENT_MA, GAMMA, HDR, IDLE_CURRENT_MA,
+    POWER_FEEDS, PROTOCOL, TEMPERATURE, WHITE_BALANCE
+};
+use lumio::correction::Correction;
+use lumio::power::PowerBudget;
+use lumio::layout::Layout;
+use lumio::ledstrip::LEDStrip;
+use lumio::wiring::Wiring;
+use lumio::showtimer::ShowTimer;
+use lumio::button::Button;
+use lumio::link::{Handoff, Input};
+use lumio::output::Output;
+use lumio::stream::StreamDecoder;
+use lumio::text::Text;
+
+use crate::renderer::Renderer;
+
+#[cfg(not(feature = "ws2812"))]
+use crate::spi_output::SpiOutput as Driver;
+#[cfg(feature = "ws2812")]
+use crate::pio_output::PioOutput as Driver;
+
+type ButtonPin1 = Pin<gpio::bank0::Gpio21, FunctionSioInput, PullUp>;
+type ButtonPin2 = Pin<gpio::bank0::Gpio20, FunctionSioInput, PullUp>;
+type LedPin1 = Pin<gpio::bank0::Gpio10, FunctionSioOutput, PullDown>;
+type LedPin2 = Pin<gpio::bank0::Gpio11, FunctionSioOutput, PullDown>;
+
+/// Repaired strips are described here, e.g.
+/// `Layout::FLOWER.with_strip_length(3, 57).with_dead_led(5, 30)`.
+#[cfg(not(feature = "half-flower"))]
+const LAYOUT: Layout = Layout::FLOWER;
+#[cfg(feature = "half-flower")]
+const LAYOUT: Layout = Layout::HALF_FLOWER;
+/// E.g. `Wiring::serpentine(LAYOUT.strips()).reverse(3)` for zig-zag wired
+/// strips with the fourth one mounted upside down.
+const WIRING: Wiring = Wiring::straight(LAYOUT.strips());
 
 // USB device configuration
 const USB_VID: u16 = 0x16c0;
@@ -55,33 +72,45 @@ const USB_MANUFACTURER: &str = "c3eo";
 const USB_PRODUCT: &str = "Lumio";
 const USB_SERIAL: &str = "001";
 
+/// The stack of core 1 in words.
+const CORE1_STACK: usize = 8192;
+/// Bytes received over USB until the next frame takes them to core 1.
+const INBOX: usize = 256;
+/// Text waiting for the host to read it, more is dropped.
+const OUTBOX: usize = 1024;
+
 
+/// Core 0: USB, the buttons and the output of the frames rendered on core 1.
 pub struct Interface {
-    led_strip: LEDStrip,
     showtimer: ShowTimer<ButtonPin1, LedPin1>,
     button: Button<ButtonPin2>,
-    led_pin: Pin<LedPin2, FunctionSioOutput, PullDown>,
-    random: Random,
-    spi0: Spi<Enabled, pac::SPI0, Spi0Pinout, 8>,
-    spi1: Spi<Enabled, pac::SPI1, Spi1Pinout, 8>,
-    delay: cortex_m::delay::Delay,
+    led_pin: LedPin2,
+    output: Driver,
     timer: Timer,
+    fifo: SioFifo,
+    input: Input,
+    stream: StreamDecoder,
+    /// Whether the host has the port open.
+    dtr: bool,
+    inbox: Text<INBOX>,
+    /// Whether bytes were lost since the inbox was handed over.
+    lost: bool,
+    outbox: Text<OUTBOX>,
     usb_serial: SerialPort<'static, UsbBus>,
     usb_dev: UsbDevice<'static, UsbBus>,
 }
 
 impl Interface {
 
-    pub fn get_time(&self) -> Instant {
-        self.timer.get_counter()
-    }
-
-    pub fn new() -> Interface {
+    /// Sets up the board and starts `render` on core 1.
+    pub fn new<F>(render: F) -> Interface
+    where
+        F: FnOnce(Renderer) + Send + 'static
+    {
 
         let mut pac = pac::Peripherals::take().unwrap();
-        let core = pac::CorePeripherals::take().unwrap();
         let mut watchdog = Watchdog::new(pac.WATCHDOG);
-        let sio = Sio::new(pac.SIO);
+        let mut sio = Sio::new(pac.SIO);
 
         // External high-speed crystal on the pico board is 12Mhz
         let external_xtal_freq_hz = 12_000_000u32;
@@ -109,27 +138,49 @@ impl Interface {
         let led_1_pin = pins.gpio10.into_push_pull_output();
         let led_2_pin = pins.gpio11.into_push_pull_output();
 
-        let sclk = pins.gpio6.into_function::<FunctionSpi>();
-        let mosi = pins.gpio7.into_function::<FunctionSpi>();
-
-        let spi_device = pac.SPI0;
-        let spi_pin_layout = (mosi, sclk);
-
-        let spi0 = Spi::<_, _, _, 8>::new(spi_device, spi_pin_layout)
-            .init(&mut pac.RESETS, PERI_FEQUENCY.Hz(), BAUD_RATE.Hz(), MODE_0);
-
-        let sclk = pins.gpio14.into_function::<FunctionSpi>();
-        let mosi = pins.gpio15.into_function::<FunctionSpi>();
-
-        let spi_device = pac.SPI1;
-        let spi_pin_layout = (mosi, sclk);
-
-        let spi1 = Spi::<_, _, _, 8>::new(spi_device, spi_pin_layout)
-            .init(&mut pac.RESETS, PERI_FEQUENCY.Hz(), BAUD_RATE.Hz(), MODE_0);
-
         let timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+        #[cfg(feature = "ws2812")]
         let system_freq = clocks.system_clock.freq().to_Hz();
 
+        let led_strip = cortex_m::singleton!(: LEDStrip = LEDStrip::new(LAYOUT)).unwrap();
+        led_strip.set_wiring(WIRING);
+        led_strip.set_protocol(PROTOCOL);
+        for (chain, order) in CHANNEL_ORDER.into_iter().enumerate() {
+            led_strip.set_channel_order(chain, order);
+        }
+        led_strip.set_brightness(BRIGHTNESS);
+        led_strip.set_hdr(HDR);
+        led_strip.set_correction(
+            Correction::new(GAMMA).with_white_balance(WHITE_BALANCE).with_temperature(TEMPERATURE)
+        );
+        led_strip.set_power_budget(
+            PowerBudget::new(FEED_CURRENT_MA, POWER_FEEDS)
+                .with_channel_current(CHANNEL_CURRENT_MA)
+                .with_idle_current(IDLE_CURRENT_MA)
+        );
+
+        let dma = pac.DMA.split(&mut pac.RESETS);
+        #[cfg(not(feature = "ws2812"))]
+        let output = Driver::new(
+            pac.SPI0,
+            (pins.gpio7.into_function(), pins.gpio6.into_function()),
+            pac.SPI1,
+            (pins.gpio15.into_function(), pins.gpio14.into_function()),
+            (dma.ch0, dma.ch1),
+            led_strip,
+            &mut pac.RESETS,
+        );
+        #[cfg(feature = "ws2812")]
+        let output = Driver::new(
+            pac.PIO0,
+            (pins.gpio7.into_function(), pins.gpio15.into_function()),
+            (dma.ch0, dma.ch1),
+            led_strip,
+            &mut pac.RESETS,
+            system_freq,
+            timer,
+        );
+
         // Take USB peripherals and clocks before they move into singleton
         let usbctrl_regs = pac.USBCTRL_REGS;
         let usbctrl_dpram = pac.USBCTRL_DPRAM;
@@ -159,47 +210,86 @@ impl Interface {
             .device_class(2) // CDC class
             .build();
 
+        let mut multicore = Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+        let core1 = &mut multicore.cores()[1];
+        let stack = cortex_m::singleton!(: Stack<CORE1_STACK> = Stack::new()).unwrap();
+        core1.spawn(&mut stack.mem, move || render(Renderer::new(led_strip, timer))).unwrap();
+
         Interface {
-            led_strip: LEDStrip::new(),
-            showtimer: ShowTimer::new(button_1, led_1_pin, timer.get_counter()),
+            showtimer: ShowTimer::new(button_1, led_1_pin),
             button: button_2,
             led_pin: led_2_pin,
-            random: Random::new(423434859),
-            spi0, spi1,
-            delay: cortex_m::delay::Delay::new(core.SYST, system_freq),
+            output,
             timer,
+            fifo: sio.fifo,
+            input: Input::new(),
+            stream: StreamDecoder::new(LAYOUT),
+            dtr: false,
+            inbox: Text::new(),
+            lost: false,
+            outbox: Text::new(),
             usb_serial,
             usb_dev,
         }
     }
 
-    pub fn led_strip(&mut self) -> &mut LEDStrip { &mut self.led_strip }
-    pub fn random(&mut self) -> &mut Random { &mut self.random }
-    pub fn do_next(&mut self) -> bool {
-        self.poll_usb();
-        self.showtimer.do_next(self.get_time())
-    }
-    pub fn button_state(&mut self) -> ButtonState { self.button.state(self.get_time()) }
-    pub fn led_on(&mut self) {
-        let _ = self.led_pin.set_high();
-    }
-    pub fn led_off(&mut self) {
-        let _ = self.led_pin.set_low();
-    }
-    pub fn write_spi(&mut self) {
-        self.led_strip.process();
-        let _ = self.spi0.write(self.led_strip.dump_0());
-        let _ = self.spi1.write(self.led_strip.dump_1());
-    }
+    /// Polls USB and writes out the next frame once core 1 hands it over.
+    /// The buttons are sampled once per frame, which debounces them.
+    pub fn serve(&mut self) {
+        if self.poll_usb() {
+            let mut buf = [0u8; 64];
+            if let Ok(len) = self.usb_read(&mut buf) {
+                let (inbox, lost) = (&mut self.inbox, &mut self.lost);
+                self.stream.push(&buf[..len], |text| *lost |= !inbox.push(text));
+                self.outbox.push(self.stream.take_replies().as_bytes());
+            }
+        }
+        // Closing the port ends OPC
+        let dtr = self.usb_serial.dtr();
+        if self.dtr && !dtr {
+            self.stream.reset();
+        }
+        self.dtr = dtr;
+        if !self.outbox.is_empty() {
+            let sent = self.usb_serial.write(self.outbox.as_bytes()).unwrap_or(0);
+            self.outbox.consume(sent);
+        }
 
-    pub fn delay_ms(&mut self, delay: u32) {
-        self.delay.delay_ms(delay);
+        let Some(address) = self.fifo.read() else {
+            return;
+        };
+        compiler_fence(Ordering::SeqCst);
+        // Core 1 keeps the handoff alive until it gets the answer
+        let handoff = unsafe { &mut *(address as *mut Handoff) };
+        self.output.write(handoff.led_strip);
+        self.outbox.push(handoff.log);
+        handoff.received.push(self.inbox.as_bytes());
+        if core::mem::take(&mut self.lost) {
+            handoff.received.lose();
+        }
+        self.inbox.clear();
+        if let Some(frame) = self.stream.take_frame() {
+            *handoff.live = *frame;
+            *handoff.fresh = true;
+        }
+        if let Some(on) = handoff.auto_show {
+            self.showtimer.set_auto_show(on);
+        }
+        let _ = if handoff.led { self.led_pin.set_high() } else { self.led_pin.set_low() };
+
+        let now = self.timer.get_counter();
+        let next = self.showtimer.do_next(now);
+        let button = self.button.state(now);
+        self.input.update(next, self.showtimer.auto_show(), button);
+        compiler_fence(Ordering::SeqCst);
+        self.fifo.write_blocking(self.input.take().to_word());
     }
 
     pub fn poll_usb(&mut self) -> bool {
         self.usb_dev.poll(&mut [&mut self.usb_serial])
     }
 
+    #[allow(dead_code)]
     pub fn usb_write(&mut self, data: &[u8]) -> Result<usize, usb_device::UsbError> {
         self.usb_serial.write(data)
     }
@@ -207,5 +297,4 @@ impl Interface {
     pub fn usb_read(&mut self, buf: &mut [u8]) -> Result<usize, usb_device::UsbError> {
         self.usb_serial.read(buf)
     }
-
 }
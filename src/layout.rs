@@ -0,0 +1,206 @@
+use crate::conf::{MAX_DEAD_LEDS, MAX_LEDS, MAX_STRIPS};
+
+/// The geometry of an installation
+///
+/// A number of strips stand side by side, LED 0 of every strip at the bottom.
+/// The shows see a grid of `length()` LEDs per strip, numbered strip by strip.
+/// If the strips form a closed circle the layout wraps around, the last strip
+/// then is the neighbour of the first one.
+///
+/// Strips may be shorter than the grid and single LEDs may be dead, bridged
+/// over in the data line.  Those positions of the grid are not sent out.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layout {
+    strips: usize,
+    length: usize,
+    wrap: bool,
+    strip_lengths: [u16; MAX_STRIPS],
+    dead: [(u8, u16); MAX_DEAD_LEDS],
+    dead_count: usize
+}
+
+impl Layout {
+    /// The full flower, 24 strips of 60 LEDs forming a funnel.
+    pub const FLOWER: Layout = Layout::new(24, 60, true);
+    /// The half circle variant with 12 strips.
+    pub const HALF_FLOWER: Layout = Layout::new(12, 60, false);
+
+    pub const fn new(strips: usize, length: usize, wrap: bool) -> Layout {
+        assert!(Layout::fits(strips, length), "layout exceeds MAX_STRIPS or MAX_LEDS");
+        Layout {
+            strips,
+            length,
+            wrap,
+            strip_lengths: [length as u16; MAX_STRIPS],
+            dead: [(0, 0); MAX_DEAD_LEDS],
+            dead_count: 0
+        }
+    }
+
+    /// Sets the number of LEDs of a single strip, the grid grows with the
+    /// longest strip.
+    pub const fn with_strip_length(mut self, strip: usize, length: usize) -> Layout {
+        assert!(strip < self.strips && length > 0);
+        self.strip_lengths[strip] = length as u16;
+        self.length = 0;
+        let mut i = 0;
+        while i < self.strips {
+            if self.strip_lengths[i] as usize > self.length {
+                self.length = self.strip_lengths[i] as usize;
+            }
+            i += 1;
+        }
+        assert!(Layout::fits(self.strips, self.length), "layout exceeds MAX_STRIPS or MAX_LEDS");
+        self
+    }
+
+    /// Marks LED `y` of `strip` as bridged over, it gets no data.
+    pub const fn with_dead_led(mut self, strip: usize, y: usize) -> Layout {
+        assert!(strip < self.strips && y < self.strip_lengths[strip] as usize);
+        assert!(!self.is_dead(strip, y), "LED is already dead");
+        assert!(self.dead_count < MAX_DEAD_LEDS, "more than MAX_DEAD_LEDS dead LEDs");
+        self.dead[self.dead_count] = (strip as u8, y as u16);
+        self.dead_count += 1;
+        self
+    }
+
+    /// Whether a layout of that size fits into the LED buffers.
+    pub const fn fits(strips: usize, length: usize) -> bool {
+        strips > 0 && length > 0 && strips <= MAX_STRIPS && strips * length <= MAX_LEDS
+    }
+
+    pub const fn strips(&self) -> usize { self.strips }
+
+    pub const fn length(&self) -> usize { self.length }
+
+    pub const fn wraps(&self) -> bool { self.wrap }
+
+    /// The number of LEDs on the grid the shows draw on.
+    pub const fn num_leds(&self) -> usize {
+        self.strips * self.length
+    }
+
+    pub const fn strip_length(&self, strip: usize) -> usize {
+        self.strip_lengths[strip] as usize
+    }
+
+    pub const fn is_dead(&self, strip: usize, y: usize) -> bool {
+        let mut i = 0;
+        while i < self.dead_count {
+            if self.dead[i].0 as usize == strip && self.dead[i].1 as usize == y {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    /// Whether there is a working LED at that position of the grid.
+    pub const fn has_led(&self, strip: usize, y: usize) -> bool {
+        y < self.strip_length(strip) && !self.is_dead(strip, y)
+    }
+
+    /// The number of LEDs that are sent out.
+    pub const fn physical_leds(&self) -> usize {
+        let mut leds = 0;
+        let mut i = 0;
+        while i < self.strips {
+            leds += self.strip_lengths[i] as usize;
+            i += 1;
+        }
+        leds - self.dead_count
+    }
+
+    /// The index of LED `y` of `strip`.
+    pub fn index(&self, strip: usize, y: usize) -> usize {
+        strip * self.length + y
+    }
+
+    /// Like `index`, but for positions that may be off the strip.
+    pub fn pos(&self, strip: isize, y: isize) -> isize {
+        strip * self.length as isize + y
+    }
+
+    /// The strip `offset` strips beside `strip`, `None` if that is beyond the
+    /// edge of a layout that does not wrap around.
+    pub fn neighbour(&self, strip: usize, offset: isize) -> Option<usize> {
+        let strips = self.strips as isize;
+        let neighbour = strip as isize + offset;
+        if self.wrap {
+            Some(neighbour.rem_euclid(strips) as usize)
+        } else if (0..strips).contains(&neighbour) {
+            Some(neighbour as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn neighbours_stay_on_the_layout(strip in 0..MAX_STRIPS, offset in -100isize..100, wrap: bool) {
+            let layout = Layout::new(MAX_STRIPS, 10, wrap);
+            if let Some(neighbour) = layout.neighbour(strip, offset) {
+                prop_assert!(neighbour < layout.strips());
+            }
+        }
+    }
+
+    #[test]
+    fn wrapping_layout_is_closed() {
+        let layout = Layout::FLOWER;
+        assert_eq!(layout.neighbour(0, -1), Some(23));
+        assert_eq!(layout.neighbour(23, 1), Some(0));
+        assert_eq!(layout.neighbour(5, 24), Some(5));
+    }
+
+    #[test]
+    fn open_layout_has_edges() {
+        let layout = Layout::HALF_FLOWER;
+        assert_eq!(layout.neighbour(0, -1), None);
+        assert_eq!(layout.neighbour(11, 1), None);
+        assert_eq!(layout.neighbour(10, 1), Some(11));
+    }
+
+    #[test]
+    fn leds_are_numbered_strip_by_strip() {
+        let layout = Layout::new(3, 5, false);
+        assert_eq!(layout.num_leds(), 15);
+        assert_eq!(layout.index(2, 4), 14);
+        assert_eq!(layout.pos(-1, 2), -3);
+        assert!(!Layout::fits(MAX_STRIPS + 1, 1));
+        assert!(!Layout::fits(1, MAX_LEDS + 1));
+    }
+
+    #[test]
+    fn grid_grows_with_the_longest_strip() {
+        let layout = Layout::new(3, 5, false).with_strip_length(1, 7).with_strip_length(2, 4);
+        assert_eq!(layout.length(), 7);
+        assert_eq!(layout.num_leds(), 21);
+        assert_eq!(layout.physical_leds(), 16);
+        assert_eq!(layout.index(1, 0), 7);
+        assert!(layout.has_led(1, 6));
+        assert!(!layout.has_led(0, 6));
+    }
+
+    #[test]
+    fn dead_leds_are_not_sent() {
+        let layout = Layout::new(2, 5, true).with_dead_led(0, 2).with_dead_led(1, 4);
+        assert_eq!(layout.physical_leds(), 8);
+        assert!(layout.is_dead(0, 2));
+        assert!(!layout.has_led(1, 4));
+        assert!(layout.has_led(1, 2));
+        assert_eq!(layout.num_leds(), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "already dead")]
+    fn dead_led_only_once() {
+        Layout::FLOWER.with_dead_led(3, 3).with_dead_led(3, 3);
+    }
+}
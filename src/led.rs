@@ -1,10 +1,10 @@
-use rp_pico::hal::rom_data::float_funcs::float_to_uint;
 use libm::fabsf;
 
-use crate::math8::{scale8,qadd8,qsub8};
+use crate::frameclock::Delta;
+use crate::math8::{scale8,qadd8};
 use crate::random;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -39,20 +39,20 @@ impl Color {
         let h6 = (h % 1.0) *6.;
         let x = c * (1. - fabsf(h6 % 2. - 1.));
         let (r, g, b) = match h6 {
-            h if h >= 0.0 && h < 1.0 => (c, x, N),
-            h if h >= 1.0 && h < 2.0 => (x, c, N),
-            h if h >= 2.0 && h < 3.0 => (N, c, x),
-            h if h >= 3.0 && h < 4.0 => (N, x, c),
-            h if h >= 4.0 && h < 5.0 => (x, N, c),
-            h if h >= 5.0 && h <= 6.0 => (c, N, x),
+            h if (0.0..1.0).contains(&h) => (c, x, N),
+            h if (1.0..2.0).contains(&h) => (x, c, N),
+            h if (2.0..3.0).contains(&h) => (N, c, x),
+            h if (3.0..4.0).contains(&h) => (N, x, c),
+            h if (4.0..5.0).contains(&h) => (x, N, c),
+            h if (5.0..=6.0).contains(&h) => (c, N, x),
             _ => (N, N, N)
         };
 
         let m = v - c;
         Color {
-            r: float_to_uint((r+m) * 255.0) as u8,
-            g: float_to_uint((g+m) * 255.0) as u8,
-            b: float_to_uint((b+m) * 255.0) as u8,
+            r: ((r+m) * 255.0) as u8,
+            g: ((g+m) * 255.0) as u8,
+            b: ((b+m) * 255.0) as u8,
         }
     }
 
@@ -104,25 +104,23 @@ impl Led {
         self.flicker = flicker;
     }
 
-    pub fn step(&mut self, random: &mut random::Random) {
-        match self.target {
-            Some(target) => {
-                self.current.r = decay(self.current.r, target.r, self.decay);
-                self.current.g = decay(self.current.g, target.g, self.decay);
-                self.current.b = decay(self.current.b, target.b, self.decay);
-                let is_dark = self.current.brightness() < target.brightness();
-                if self.current == target || is_dark  && self.decay != 0 {
-                    self.flicker = 0;
-                    self.target = None;
-                }
-            },
-            None => {
+    /// Moves on towards the target by `dt`, the decay is the rate of a frame.
+    pub fn step(&mut self, random: &mut random::Random, dt: Delta) {
+        if let Some(target) = self.target {
+            let rate = dt.decay(self.decay);
+            self.current.r = decay(self.current.r, target.r, rate);
+            self.current.g = decay(self.current.g, target.g, rate);
+            self.current.b = decay(self.current.b, target.b, rate);
+            let is_dark = self.current.brightness() < target.brightness();
+            if self.current == target || is_dark  && self.decay != 0 {
+                self.flicker = 0;
+                self.target = None;
             }
         }
 
         if self.flicker != 0 {
             let brightness = self.current.brightness();
-            let offset = 0xff - brightness >> 1;
+            let offset = (0xff - brightness) >> 1;
             self.current_flicker = scale8(random.value8(), brightness) + offset;
         }
     }
@@ -173,3 +171,84 @@ pub fn decay(current: u8, target: u8, decay: u8) -> u8 {
         current - (((current - target) as u16 * decay as u16) >> 8).max(1) as u8
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fugit::MicrosDurationU64;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn decay_moves_towards_target_without_overshooting(current: u8, target: u8, rate: u8) {
+            let next = decay(current, target, rate);
+            prop_assert!(next >= current.min(target) && next <= current.max(target));
+            if current != target {
+                prop_assert_ne!(next, current);
+            }
+        }
+
+        #[test]
+        fn decay_reaches_target(current: u8, target: u8, rate: u8) {
+            let mut value = current;
+            for _ in 0..256 {
+                value = decay(value, target, rate);
+            }
+            prop_assert_eq!(value, target);
+        }
+
+        #[test]
+        fn from_hsv_is_bounded_by_value(h in 0.0f32..10.0, s in 0.0f32..=1.0, v in 0.0f32..=1.0) {
+            let color = Color::from_hsv(h, s, v);
+            let max = (v * 255.0) as u8;
+            prop_assert!(color.r <= max && color.g <= max && color.b <= max);
+        }
+
+        #[test]
+        fn from_hsv_without_saturation_is_grey(h in 0.0f32..10.0, v in 0.0f32..=1.0) {
+            let color = Color::from_hsv(h, 0.0, v);
+            prop_assert!(color.r == color.g && color.g == color.b);
+        }
+
+        #[test]
+        fn from_hsv_repeats_every_turn(h in 0.0f32..1.0, turns in 1u8..8) {
+            let color = Color::from_hsv(h, 1.0, 1.0);
+            let turned = Color::from_hsv(h + turns as f32, 1.0, 1.0);
+            prop_assert!(color.r.abs_diff(turned.r) <= 1);
+            prop_assert!(color.g.abs_diff(turned.g) <= 1);
+            prop_assert!(color.b.abs_diff(turned.b) <= 1);
+        }
+    }
+
+    #[test]
+    fn two_half_steps_match_one_step() {
+        let half = Delta::new(MicrosDurationU64::millis(10), MicrosDurationU64::millis(20));
+        let mut random = random::Random::new(1);
+        for (current, target, rate) in [(255, 0, 128), (200, 10, 64), (255, 0, 16), (120, 100, 200), (255, 0, 2)] {
+            let grey = |v| Color { r: v, g: v, b: v };
+            let mut once = Led::new();
+            once.set_color(grey(current));
+            once.set_target(grey(target), rate);
+            let mut twice = once;
+            once.step(&mut random, Delta::ONE);
+            twice.step(&mut random, half);
+            twice.step(&mut random, half);
+            assert!(once.current().r.abs_diff(twice.current().r) <= 1, "{current} {target} {rate}");
+        }
+    }
+
+    #[test]
+    fn decay_full_rate_jumps_close_to_target() {
+        assert_eq!(decay(0, 255, 255), 254);
+        assert_eq!(decay(255, 0, 255), 1);
+        assert_eq!(decay(10, 10, 128), 10);
+    }
+
+    #[test]
+    fn from_hsv_primaries() {
+        assert!(Color::from_hsv(0.0, 1.0, 1.0) == RED);
+        assert!(Color::from_hsv(1.0 / 3.0, 1.0, 1.0) == GREEN);
+        assert!(Color::from_hsv(2.0 / 3.0, 1.0, 1.0) == BLUE);
+        assert!(Color::from_hsv(0.5, 1.0, 0.0) == BLACK);
+    }
+}
@@ -1,40 +1,172 @@
+use crate::apa102;
+use crate::protocol::{ChannelOrder, Frame, Protocol};
 use crate::conf::*;
+use crate::correction::Correction;
+use crate::power::{PowerBudget, PowerLimiter, PowerReport};
+use crate::layout::Layout;
+use crate::wiring::Wiring;
 use crate::led::{Led, Color, BLACK};
 use crate::random::Random;
+use crate::transition::Mix;
+use crate::frameclock::Delta;
 
-const HALF: usize = NUM_LED / 2;
-const DATA_SIZE: usize = NUM_LED*4+8;
-const HALF_BYTES: usize = DATA_SIZE / 2;
-const LED_DATA_SIZE: usize = 4;
-const SPI_OFFSET: usize = 4;
-const RED_OFFSET: usize = 3;
-const GREEN_OFFSET: usize = 2;
-const BLUE_OFFSET: usize = 1;
+const DATA_SIZE: usize = Frame::max_len(MAX_LEDS, 2);
 
 pub struct LEDStrip {
+    layout: Layout,
+    /// The logical LED index of every physical LED.
+    wiring_map: [u16; MAX_LEDS],
     bytes: [u8; DATA_SIZE],
-    leds: [Led; NUM_LED],
-    random: Random
+    leds: [Led; MAX_LEDS],
+    random: Random,
+    protocol: Protocol,
+    /// Channel order of the two chains.
+    channel_order: [ChannelOrder; 2],
+    brightness: u8,
+    hdr: bool,
+    correction: Correction,
+    power: PowerLimiter,
+    /// The colours of the frame sent out last, mix included, by logical index.
+    shown: [Color; MAX_LEDS],
+    /// The frame a transition starts from, by logical index.
+    previous: [Color; MAX_LEDS],
+    mix: Option<Mix>,
+    /// How far the LEDs step on towards their targets after a frame.
+    delta: Delta
 }
 
 
 impl LEDStrip {
-    pub fn new() -> LEDStrip {
+    pub fn new(layout: Layout) -> LEDStrip {
         let bytes: [u8; DATA_SIZE] = [0x00u8; DATA_SIZE];
-        let leds = [Led::new(); NUM_LED];
-        LEDStrip { bytes, leds, random: Random::new(423234098) }
+        let leds = [Led::new(); MAX_LEDS];
+        let mut led_strip = LEDStrip {
+            layout,
+            wiring_map: [0; MAX_LEDS],
+            bytes,
+            leds,
+            random: Random::new(423234098),
+            protocol: Protocol::Apa102,
+            channel_order: [ChannelOrder::Bgr; 2],
+            brightness: 255,
+            hdr: false,
+            correction: Correction::linear(),
+            power: PowerLimiter::new(PowerBudget::unlimited()),
+            shown: [BLACK; MAX_LEDS],
+            previous: [BLACK; MAX_LEDS],
+            mix: None,
+            delta: Delta::ONE
+        };
+        led_strip.set_wiring(Wiring::straight(layout.strips()));
+        led_strip
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn set_wiring(&mut self, wiring: Wiring) {
+        assert_eq!(wiring.strips(), self.layout.strips(), "wiring does not match the layout");
+        for (physical, logical) in wiring.physical_to_logical(self.layout).enumerate() {
+            self.wiring_map[physical] = logical as u16;
+        }
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// The LED chips, they take different data.
+    pub fn set_protocol(&mut self, protocol: Protocol) {
+        self.protocol = protocol;
+    }
+
+    pub fn channel_order(&self, chain: usize) -> ChannelOrder {
+        self.channel_order[chain]
+    }
+
+    /// The channel order of the strips on chain 0 or 1.
+    pub fn set_channel_order(&mut self, chain: usize, order: ChannelOrder) {
+        self.channel_order[chain] = order;
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Master brightness, applied through the global brightness of the LEDs.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    pub fn hdr(&self) -> bool {
+        self.hdr
+    }
+
+    /// Spread dark colours across the global brightness and the channels.
+    pub fn set_hdr(&mut self, hdr: bool) {
+        self.hdr = hdr;
+    }
+
+    pub fn correction(&self) -> &Correction {
+        &self.correction
+    }
+
+    /// Gamma and colour correction of everything sent to the strips.
+    pub fn set_correction(&mut self, correction: Correction) {
+        self.correction = correction;
+    }
+
+    pub fn power(&self) -> &PowerLimiter {
+        &self.power
+    }
+
+    /// Dims the frames that would draw more current than the budget.
+    pub fn set_power_budget(&mut self, budget: PowerBudget) {
+        self.power = PowerLimiter::new(budget);
+    }
+
+    /// A change of the power limiting since the last call, if any.
+    pub fn take_power_report(&mut self) -> Option<PowerReport> {
+        self.power.take_report()
+    }
+
+    /// Keeps the frame sent out last, mix included, as the frame a transition
+    /// starts from.  The LEDs have stepped on since, so it is taken from what
+    /// `process()` sent.
+    pub fn keep_frame(&mut self) {
+        let num_leds = self.layout.num_leds();
+        self.previous[..num_leds].copy_from_slice(&self.shown[..num_leds]);
+    }
+
+    /// Mixes the frames sent out with the kept one, `None` sends the LEDs as
+    /// they are.
+    pub fn set_mix(&mut self, mix: Option<Mix>) {
+        self.mix = mix;
+    }
+
+    /// The time the frames cover, the LEDs step on towards their targets by
+    /// it after each frame sent.
+    pub fn set_delta(&mut self, dt: Delta) {
+        self.delta = dt;
     }
 
     pub fn set_led(&mut self, pos: isize, color: Color) {
-        let i = index_from_pos(pos);
+        let i = self.index_from_pos(pos);
         self.leds[i].set_color(color);
     }
 
     pub fn set_led_target(&mut self, pos: isize, color: Color, decay: u8) {
-        let i = index_from_pos(pos);
+        let i = self.index_from_pos(pos);
         self.leds[i].set_target(color, decay);
     }
 
+    /// The colour LED `i` got in the frame sent out last, mix included, before
+    /// the corrections of the output stage.
+    pub fn output(&self, i: usize) -> Color {
+        self.shown[i]
+    }
+
     pub fn led(&self, pos: usize) -> &Led {
         &self.leds[pos]
     }
@@ -44,38 +176,263 @@ impl LEDStrip {
     }
 
     pub fn process(&mut self) {
-        self.process_half_from(0);
-        self.process_half_from(HALF);
-    }
-
-    fn process_half_from(&mut self, start_led: usize) {
-        let start_byte = start_led * LED_DATA_SIZE + SPI_OFFSET;
-        for i in 0..HALF {
-            let led = &mut self.leds[i+start_led];
-            let offset = start_byte + i * LED_DATA_SIZE;
-            self.bytes[offset] = 0xff;
-            self.bytes[offset+BLUE_OFFSET] = led.b();
-            self.bytes[offset+GREEN_OFFSET] = led.g();
-            self.bytes[offset+RED_OFFSET] = led.r();
-            led.step(&mut self.random);
+        for i in 0..self.layout.num_leds() {
+            self.shown[i] = self.mixed(i);
+        }
+        self.limit_power();
+        let [frame_0, frame_1] = self.frames();
+        self.serialise(0, frame_0, 0);
+        self.serialise(frame_0.len(), frame_1, frame_0.leds());
+
+        let num_leds = self.layout.num_leds();
+        for led in self.leds[..num_leds].iter_mut() {
+            led.step(&mut self.random, self.delta);
+        }
+    }
+
+    /// Estimates the current of the frame about to be sent.
+    fn limit_power(&mut self) {
+        let budget = self.power.budget();
+        if budget.is_unlimited() {
+            return;
         }
+
+        let physical = self.layout.physical_leds();
+        self.power.begin();
+        for i in 0..physical {
+            let color = self.shown[self.wiring_map[i] as usize];
+            self.power.add(budget.feed(i, physical), self.correction.apply(color));
+        }
+        self.power.end(self.brightness);
+    }
+
+    /// The first half of the LEDs goes to chain 0, the second half to chain 1.
+    fn frames(&self) -> [Frame; 2] {
+        let physical = self.layout.physical_leds();
+        let half = physical / 2;
+        [
+            Frame::new(self.protocol, half).with_order(self.channel_order[0]),
+            Frame::new(self.protocol, physical - half).with_order(self.channel_order[1])
+        ]
     }
 
-    pub fn dump_0(&mut self) -> &[u8] {
-        &self.bytes[..HALF_BYTES]
+    /// The global brightness and the (red, green, blue) channels an APA102
+    /// gets for a colour.
+    pub fn encode(&self, color: Color) -> (u8, [u8; 3]) {
+        let rgb = self.power.apply(self.correction.apply(color));
+        apa102::encode(rgb, self.brightness, self.hdr)
     }
 
-    pub fn dump_1(&mut self) -> &[u8] {
-        &self.bytes[HALF_BYTES..]
+    /// Writes `frame` at `start_byte`, its LEDs are the physical LEDs from
+    /// `start_led` on in chain order.
+    fn serialise(&mut self, start_byte: usize, frame: Frame, start_led: usize) {
+        frame.write_framing(&mut self.bytes[start_byte..]);
+        for i in 0..frame.leds() {
+            let color = self.shown[self.wiring_map[i+start_led] as usize];
+            let rgb = self.power.apply(self.correction.apply(color));
+            frame.write_led(&mut self.bytes[start_byte..], i, rgb, self.brightness, self.hdr);
+        }
+    }
+
+    pub fn dump_0(&self) -> &[u8] {
+        let [frame_0, _] = self.frames();
+        &self.bytes[..frame_0.len()]
+    }
+
+    pub fn dump_1(&self) -> &[u8] {
+        let [frame_0, frame_1] = self.frames();
+        &self.bytes[frame_0.len()..frame_0.len() + frame_1.len()]
     }
 
     pub fn black(&mut self) {
-        for led in self.leds.iter_mut() {
+        let num_leds = self.layout.num_leds();
+        for led in self.leds[..num_leds].iter_mut() {
             led.set_color(BLACK);
         }
     }
+
+    /// The colour to send out for the LED with logical index `i`.
+    fn mixed(&self, i: usize) -> Color {
+        let color = self.leds[i].current();
+        match self.mix {
+            Some(mix) => mix.apply(&self.layout, i, self.previous[i], color),
+            None => color
+        }
+    }
+
+    fn index_from_pos(&self, pos: isize) -> usize {
+        index_from_pos(pos, self.layout.num_leds())
+    }
+}
+
+fn index_from_pos(pos: isize, num_leds: usize) -> usize {
+    pos.rem_euclid(num_leds as isize) as usize
 }
 
-fn index_from_pos(pos: isize) -> usize {
-    (if pos < 0 { NUM_LED - pos.abs() as usize } else { pos as usize }) % NUM_LED
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{LED_SIZE, START_FRAME};
+    use crate::led::WHITE;
+    use crate::transition::Blend;
+    use proptest::prelude::*;
+
+    /// The red channels sent down both chains.
+    fn reds(led_strip: &mut LEDStrip) -> Vec<u8> {
+        let mut reds = Vec::new();
+        for dump in [led_strip.dump_0().to_vec(), led_strip.dump_1().to_vec()] {
+            let leds = dump[START_FRAME..].chunks(LED_SIZE).take_while(|led| led[0] & 0xe0 == 0xe0);
+            reds.extend(leds.map(|led| led[3]));
+        }
+        reds
+    }
+
+    fn first_led(led_strip: &mut LEDStrip) -> Vec<u8> {
+        led_strip.dump_0()[START_FRAME..START_FRAME + LED_SIZE].to_vec()
+    }
+
+    proptest! {
+        #[test]
+        fn index_from_pos_is_in_range(pos: isize, num_leds in 1..=MAX_LEDS) {
+            prop_assert!(index_from_pos(pos, num_leds) < num_leds);
+        }
+
+        #[test]
+        fn index_from_pos_wraps_around(pos in -4 * MAX_LEDS as isize..4 * MAX_LEDS as isize, num_leds in 1..=MAX_LEDS) {
+            prop_assert_eq!(index_from_pos(pos + num_leds as isize, num_leds), index_from_pos(pos, num_leds));
+        }
+    }
+
+    #[test]
+    fn index_from_pos_limits() {
+        let num_leds = Layout::FLOWER.num_leds();
+        assert_eq!(index_from_pos(0, num_leds), 0);
+        assert_eq!(index_from_pos(num_leds as isize - 1, num_leds), num_leds - 1);
+        assert_eq!(index_from_pos(num_leds as isize, num_leds), 0);
+        assert_eq!(index_from_pos(-1, num_leds), num_leds - 1);
+        assert_eq!(index_from_pos(-(num_leds as isize) - 1, num_leds), num_leds - 1);
+    }
+
+    #[test]
+    fn dumps_cover_the_layout() {
+        let mut led_strip = LEDStrip::new(Layout::new(3, 5, false));
+        led_strip.process();
+        assert_eq!(led_strip.dump_0(), [&[0; 4], &[0xff, 0, 0, 0].repeat(7)[..], &[0]].concat());
+        assert_eq!(led_strip.dump_1(), [&[0; 4], &[0xff, 0, 0, 0].repeat(8)[..], &[0]].concat());
+
+        led_strip.set_protocol(Protocol::Sk9822);
+        led_strip.process();
+        assert_eq!(led_strip.dump_0().len(), 4 + 7 * 4 + 5);
+        assert_eq!(led_strip.dump_1().len(), 4 + 8 * 4 + 5);
+    }
+
+    #[test]
+    fn wiring_is_applied_when_serialising() {
+        let layout = Layout::new(2, 2, true);
+        let mut led_strip = LEDStrip::new(layout);
+        led_strip.set_wiring(Wiring::with_order(&[1, 0]).reverse(1));
+        for i in 0..4 {
+            led_strip.set_led(i, Color { r: i as u8 + 1, g: 0, b: 0 });
+        }
+        led_strip.process();
+        assert_eq!(reds(&mut led_strip), [4, 3, 1, 2]);
+    }
+
+    #[test]
+    fn chains_have_their_own_channel_order() {
+        let mut led_strip = LEDStrip::new(Layout::new(2, 1, false));
+        led_strip.set_channel_order(1, ChannelOrder::Rgb);
+        led_strip.set_led(0, Color { r: 1, g: 2, b: 3 });
+        led_strip.set_led(1, Color { r: 1, g: 2, b: 3 });
+        led_strip.process();
+        assert_eq!(led_strip.dump_0()[START_FRAME..START_FRAME + LED_SIZE], [0xff, 3, 2, 1]);
+        assert_eq!(led_strip.dump_1()[START_FRAME..START_FRAME + LED_SIZE], [0xff, 1, 2, 3]);
+    }
+
+    #[test]
+    fn brightness_goes_into_the_header() {
+        let mut led_strip = LEDStrip::new(Layout::new(1, 2, false));
+        led_strip.set_led(0, Color { r: 0, g: 0, b: 16 });
+        led_strip.process();
+        assert_eq!(first_led(&mut led_strip), [0xff, 16, 0, 0]);
+
+        led_strip.set_brightness(128);
+        led_strip.process();
+        assert_eq!(first_led(&mut led_strip), [0xf0, 16, 0, 0]);
+
+        led_strip.set_brightness(255);
+        led_strip.set_hdr(true);
+        led_strip.process();
+        assert_eq!(first_led(&mut led_strip), [0xe2, 248, 0, 0]);
+    }
+
+    #[test]
+    fn correction_is_applied_when_serialising() {
+        let mut led_strip = LEDStrip::new(Layout::new(1, 2, false));
+        led_strip.set_led(0, Color { r: 128, g: 255, b: 255 });
+        led_strip.set_correction(Correction::new(2.2).with_white_balance(Color { r: 255, g: 255, b: 128 }));
+        led_strip.process();
+        assert_eq!(first_led(&mut led_strip), [0xff, 128, 255, 56]);
+    }
+
+    #[test]
+    fn frames_over_the_power_budget_are_dimmed() {
+        let mut led_strip = LEDStrip::new(Layout::new(2, 5, false));
+        // 10 white LEDs take 610 mA
+        led_strip.set_power_budget(PowerBudget::new(160, 2));
+        for i in 0..10 {
+            led_strip.set_led(i, WHITE);
+        }
+        led_strip.process();
+        assert_eq!(led_strip.power().feed_current(), [305, 305]);
+        assert_eq!(first_led(&mut led_strip), [0xff, 132, 132, 132]);
+        assert!(matches!(led_strip.take_power_report(), Some(PowerReport::Limiting { percent: 51, .. })));
+
+        led_strip.black();
+        for _ in 0..128 {
+            led_strip.process();
+        }
+        assert_eq!(led_strip.take_power_report(), Some(PowerReport::WithinBudget));
+    }
+
+    #[test]
+    fn missing_leds_are_not_sent() {
+        let layout = Layout::new(2, 3, true).with_strip_length(1, 2).with_dead_led(0, 0);
+        let mut led_strip = LEDStrip::new(layout);
+        for i in 0..6 {
+            led_strip.set_led(i, Color { r: i as u8 + 1, g: 0, b: 0 });
+        }
+        led_strip.process();
+        assert_eq!(reds(&mut led_strip), [2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn kept_frames_are_mixed_into_the_output() {
+        let mut led_strip = LEDStrip::new(Layout::new(2, 3, true));
+        led_strip.set_led(0, WHITE);
+        led_strip.process();
+        led_strip.keep_frame();
+        led_strip.black();
+        led_strip.set_mix(Some(Mix::new(Blend::Crossfade, 128)));
+        led_strip.process();
+        assert_eq!(reds(&mut led_strip)[0], 127);
+
+        led_strip.set_mix(None);
+        led_strip.process();
+        assert_eq!(reds(&mut led_strip)[0], 0);
+    }
+
+    #[test]
+    fn the_kept_frame_is_the_one_sent_out() {
+        let mut led_strip = LEDStrip::new(Layout::new(2, 3, true));
+        led_strip.set_led(0, WHITE);
+        led_strip.set_led_target(0, BLACK, 128);
+        led_strip.process();
+        assert!(led_strip.led(0).current().r < 255);
+        led_strip.keep_frame();
+        led_strip.black();
+        led_strip.set_mix(Some(Mix::new(Blend::Crossfade, 0)));
+        led_strip.process();
+        assert_eq!(reds(&mut led_strip)[0], 255);
+    }
 }
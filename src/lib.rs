@@ -0,0 +1,45 @@
+//! The hardware independent part of the LED strip flower
+//!
+//! All the shows and the LED strip model live here.  They only see the
+//! installation through the `hardware::Hardware` trait, so they can run on the
+//! RP2040 as well as in the desktop simulator.
+#![cfg_attr(not(test), no_std)]
+#![allow(clippy::new_without_default)]
+
+pub mod conf;
+pub mod layout;
+pub mod wiring;
+pub mod hardware;
+pub mod button;
+pub mod showtimer;
+pub mod frameclock;
+pub mod math8;
+pub mod led;
+pub mod protocol;
+pub mod apa102;
+pub mod ws2812;
+pub mod correction;
+pub mod power;
+pub mod ledstrip;
+pub mod output;
+pub mod link;
+pub mod random;
+pub mod text;
+pub mod command;
+pub mod stream;
+pub mod opc;
+pub mod upload;
+pub mod show;
+pub mod programme;
+pub mod playlist;
+pub mod transition;
+pub mod snake;
+pub mod fire;
+pub mod stars;
+pub mod spiral;
+pub mod huewave;
+pub mod sparks;
+pub mod particle_crash;
+pub mod sine;
+pub mod planets;
+pub mod snow;
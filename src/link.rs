@@ -0,0 +1,158 @@
+//! What the two cores of the RP2040 tell each other
+//!
+//! Core 1 renders the shows, core 0 runs USB, the buttons and the output.
+//! After every frame core 1 hands a `Handoff` over through the SIO FIFO and
+//! waits until core 0 has copied the frame and passed on what came in over
+//! USB.  The answer is the `Input` gathered on core 0 since the last frame,
+//! packed into one FIFO word.
+
+use crate::button::ButtonState;
+use crate::command::LineReader;
+use crate::ledstrip::LEDStrip;
+use crate::stream::LiveFrame;
+
+/// A rendered frame and what else core 1 wants done with it.
+pub struct Handoff<'a> {
+    pub led_strip: &'a LEDStrip,
+    /// Text for the USB serial port.
+    pub log: &'a [u8],
+    /// The state of the LED of the second button.
+    pub led: bool,
+    /// Turns the automatic change of the shows on or off.
+    pub auto_show: Option<bool>,
+    /// Core 0 adds the command bytes received over USB.
+    pub received: &'a mut LineReader,
+    /// Core 0 puts the latest frame streamed over USB here and sets `fresh`.
+    pub live: &'a mut LiveFrame,
+    pub fresh: &'a mut bool
+}
+
+/// The show timer and the second button, as seen by the shows.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Input {
+    next: bool,
+    auto: bool,
+    button: ButtonState
+}
+
+const NEXT: u32 = 1 << 8;
+const AUTO: u32 = 1 << 9;
+
+impl Input {
+    pub const fn new() -> Input {
+        Input { next: false, auto: true, button: ButtonState::Up }
+    }
+
+    /// Adds newer input.  A request for the next show and a press of the
+    /// button are kept until they are taken.
+    pub fn update(&mut self, next: bool, auto: bool, button: ButtonState) {
+        self.next |= next;
+        self.auto = auto;
+        if !is_press(self.button) {
+            self.button = button;
+        }
+    }
+
+    /// Adds input received from the other core.
+    pub fn merge(&mut self, other: Input) {
+        self.update(other.next, other.auto, other.button);
+    }
+
+    /// Whether the shows change on their own.
+    pub fn auto(&self) -> bool {
+        self.auto
+    }
+
+    /// Whether the next show is due, only once per request.
+    pub fn take_next(&mut self) -> bool {
+        core::mem::take(&mut self.next)
+    }
+
+    /// The state of the button, a press only once.
+    pub fn take_button(&mut self) -> ButtonState {
+        let button = self.button;
+        if is_press(button) {
+            self.button = ButtonState::Up;
+        }
+        button
+    }
+
+    /// Hands all of the input over, leaving the current state of the button.
+    pub fn take(&mut self) -> Input {
+        let input = *self;
+        self.take_next();
+        self.take_button();
+        input
+    }
+
+    pub fn to_word(self) -> u32 {
+        let button = match self.button {
+            ButtonState::Up => 0,
+            ButtonState::Down => 1,
+            ButtonState::ShortPressed => 2,
+            ButtonState::LongPressed => 3
+        };
+        let next = if self.next { NEXT } else { 0 };
+        let auto = if self.auto { AUTO } else { 0 };
+        next | auto | button
+    }
+
+    pub fn from_word(word: u32) -> Input {
+        let button = match word & 0xff {
+            1 => ButtonState::Down,
+            2 => ButtonState::ShortPressed,
+            3 => ButtonState::LongPressed,
+            _ => ButtonState::Up
+        };
+        Input { next: word & NEXT != 0, auto: word & AUTO != 0, button }
+    }
+}
+
+fn is_press(state: ButtonState) -> bool {
+    matches!(state, ButtonState::ShortPressed | ButtonState::LongPressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn state(i: u8) -> ButtonState {
+        [ButtonState::Up, ButtonState::Down, ButtonState::ShortPressed, ButtonState::LongPressed][i as usize]
+    }
+
+    #[test]
+    fn presses_are_not_lost_between_frames() {
+        let mut input = Input::new();
+        input.update(false, true, ButtonState::Down);
+        input.update(false, true, ButtonState::ShortPressed);
+        input.update(true, false, ButtonState::Up);
+        input.update(false, false, ButtonState::Up);
+
+        let sent = input.take();
+        assert_eq!(sent, Input { next: true, auto: false, button: ButtonState::ShortPressed });
+        assert_eq!(input, Input { auto: false, ..Input::new() });
+    }
+
+    #[test]
+    fn presses_are_taken_once() {
+        let mut input = Input::new();
+        input.merge(Input::from_word(NEXT | 3));
+        assert!(input.take_next());
+        assert!(!input.take_next());
+        assert_eq!(input.take_button(), ButtonState::LongPressed);
+        assert_eq!(input.take_button(), ButtonState::Up);
+
+        input.update(false, true, ButtonState::Down);
+        assert_eq!(input.take_button(), ButtonState::Down);
+        assert_eq!(input.take_button(), ButtonState::Down);
+    }
+
+    proptest! {
+        #[test]
+        fn words_round_trip(next: bool, auto: bool, button in 0u8..4) {
+            let input = Input { next, auto, button: state(button) };
+            prop_assert_eq!(Input::from_word(input.to_word()), input);
+        }
+    }
+}
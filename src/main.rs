@@ -1,93 +1,44 @@
-//! Blinks the LED on a Pico board
+//! Firmware of the LED strip flower for the RP-Pico 2040
 //!
-//! This will blink an LED attached to GP25, which is the pin the Pico uses for the on-board LED.
+//! Sets up the board in `Interface`.  Core 1 runs the shows of the programme
+//! as the playlist says, core 0 serves USB, the buttons and the output.
 #![no_std]
 #![no_main]
 
 
-use core::usize;
 use rp_pico::entry;
 use defmt_rtt as _;
 use panic_probe as _;
 
-mod conf;
 mod interface;
-mod button;
-mod showtimer;
-mod math8;
-mod led;
-mod ledstrip;
-mod snake;
-mod random;
-mod fire;
-mod stars;
-mod spiral;
-mod huewave;
-mod sparks;
-mod particle_crash;
-mod sine;
-mod planets;
-mod snow;
+mod renderer;
+mod dma_chain;
+#[cfg(not(feature = "ws2812"))]
+mod spi_output;
+#[cfg(feature = "ws2812")]
+mod pio_output;
 
 use interface::Interface;
 
-use led::{WHITE, YELLOW, DARK_BLUE, DARK_GREEN};
-use snake::SnakeShow;
-use fire::Fire;
-use stars::Stars;
-use spiral::HueSpiral;
-use sparks::{FireWorks, SparkFall, SnowSparks};
-use particle_crash::ParticleCrash;
-use sine::{SeaWave, SineShow};
-use planets::PlanetShow;
-use snow::snow;
+use lumio::conf::PLAYLIST;
+use lumio::programme::Programme;
+use lumio::show::run_next;
 
 #[entry]
 fn main() -> ! {
-    let mut interface = Interface::new();
-
-    let mut hue_spiral = HueSpiral::new();
-    let mut fireworks = FireWorks::new();
-    let mut fire_red = Fire::new_red();
-    let mut fire_green = Fire::new_green();
-    let mut eu_stars = Stars::new(DARK_BLUE, YELLOW);
-    let mut eo_stars = Stars::new(DARK_GREEN, WHITE);
-    let mut falling_sparks = SparkFall::new();
-    let mut snow_sparks = SnowSparks::new();
-    let mut snake_show = SnakeShow::new();
-    let mut particle_crash = ParticleCrash::new();
-    let mut sine_show = SineShow::new();
-    let mut planet_show = PlanetShow::new();
-    let mut sea_wave_chaos = SeaWave::new(0.63, 0.5, Some(9800), 8);
-    let mut sea_wave_order = SeaWave::new(0.99, 0.99, Some(1050), 12);
-    //let mut sea_wave_varying = SeaWave::new(None, 12);
+    let programme = cortex_m::singleton!(: Programme = Programme::new()).unwrap();
+    let mut interface = Interface::new(move |mut renderer| {
+        let mut playlist = PLAYLIST;
+        let mut shows = programme.shows();
+        loop {
+            run_next(&mut playlist, &mut shows, &mut renderer);
+        }
+    });
 
     interface.poll_usb();
 
     loop {
-//        sea_wave_varying.show(&mut interface);
-        interface.usb_write(b"snow\n");
-        snow::<90>(&mut interface);
-        interface.usb_write(b"sine\n");
-        sine_show.show(&mut interface);
-        interface.usb_write(b"particles\n");
-        particle_crash.show_spiral(&mut interface);
-        interface.usb_write(b"planets\n");
-        planet_show.show(&mut interface);
-        fire_green.show(&mut interface);
-        hue_spiral.show_lift(&mut interface);
-        snow::<180>(&mut interface);
-        sea_wave_chaos.show(&mut interface);
-        fireworks.show(&mut interface);
-        hue_spiral.show_swirl(&mut interface);
-        snow_sparks.show(&mut interface);
-        eo_stars.show(&mut interface);
-        particle_crash.show(&mut interface);
-        sea_wave_order.show(&mut interface);
-        snake_show.show(&mut interface);
-        falling_sparks.show(&mut interface);
-        eu_stars.show(&mut interface);
-        fire_red.show(&mut interface);
+        interface.serve();
     }
 }
 
@@ -4,11 +4,7 @@ pub fn scale8(a: u8, b: u8) -> u8 {
 }
 
 pub fn qsub8(a: u8, b: u8) -> u8 {
-    if a <= b {
-        0u8
-    } else {
-        a - b
-    }
+    a.saturating_sub(b)
 }
 
 pub fn qadd8(a: u8, b: u8) -> u8 {
@@ -17,3 +13,39 @@ pub fn qadd8(a: u8, b: u8) -> u8 {
     }
     a + b
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn qadd8_saturates(a: u8, b: u8) {
+            prop_assert_eq!(qadd8(a, b), a.saturating_add(b));
+        }
+
+        #[test]
+        fn qsub8_saturates(a: u8, b: u8) {
+            prop_assert_eq!(qsub8(a, b), a.saturating_sub(b));
+        }
+
+        #[test]
+        fn scale8_never_exceeds_its_inputs(a: u8, b: u8) {
+            prop_assert!(scale8(a, b) <= a.min(b));
+            prop_assert_eq!(scale8(a, b), scale8(b, a));
+        }
+
+        #[test]
+        fn scale8_is_monotonic(a: u8, b: u8, c: u8) {
+            prop_assert!(scale8(a.min(c), b) <= scale8(a.max(c), b));
+        }
+    }
+
+    #[test]
+    fn scale8_limits() {
+        assert_eq!(scale8(200, 0), 0);
+        assert_eq!(scale8(200, 128), 100);
+        assert_eq!(scale8(255, 255), 254);
+    }
+}
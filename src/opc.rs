@@ -0,0 +1,220 @@
+//! Open Pixel Control
+//!
+//! After the `opc` command the USB serial port takes OPC messages instead of
+//! text, until the host closes the port.  A message is a header of four bytes
+//! and its data:
+//!
+//! ```text
+//! channel (u8) command (u8) length (u16, big endian) data...
+//! ```
+//!
+//! Command 0 sets the colours of the pixels, RGB.  Channel 0 addresses all LEDs
+//! in the order of their `LEDStrip` index, channel n the LEDs of strip n from
+//! the bottom.  System exclusive messages (command 255) start with the ID of
+//! the system they are meant for, there is none for the flower, so they are
+//! skipped like any other command.
+
+use crate::layout::Layout;
+use crate::led::Color;
+use crate::stream::LiveFrame;
+
+const SET_PIXELS: u8 = 0;
+const SYSTEM_EXCLUSIVE: u8 = 255;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command {
+    SetPixels,
+    /// With the ID of the system it is meant for.
+    SystemExclusive(u16),
+    Other(u8)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    Header([u8; 4], usize),
+    /// The system ID of a system exclusive message.
+    SystemId([u8; 2], usize, usize),
+    Pixels { channel: u8, len: usize, received: usize },
+    Skip(usize)
+}
+
+/// Puts the pixels of the messages received into a frame, the messages may
+/// come in any pieces.
+pub struct OpcDecoder {
+    layout: Layout,
+    state: State,
+    channels: [u8; 3],
+    /// Whether the frame changed since it was last asked.
+    changed: bool,
+    last_command: Option<Command>
+}
+
+impl OpcDecoder {
+    pub const fn new(layout: Layout) -> OpcDecoder {
+        OpcDecoder {
+            layout,
+            state: State::Header([0; 4], 0),
+            channels: [0; 3],
+            changed: false,
+            last_command: None
+        }
+    }
+
+    /// Starts over with a message header and `frame` black.
+    pub fn reset(&mut self, frame: &mut LiveFrame) {
+        *self = OpcDecoder::new(self.layout);
+        frame.clear(self.layout.num_leds());
+    }
+
+    /// Sets the pixels of the messages in `bytes` in `frame`.
+    pub fn push(&mut self, bytes: &[u8], frame: &mut LiveFrame) {
+        for &byte in bytes {
+            self.decode(byte, frame);
+        }
+    }
+
+    /// Whether a message set pixels since the last call.
+    pub fn take_changed(&mut self) -> bool {
+        core::mem::take(&mut self.changed)
+    }
+
+    /// The command of the last message that came in completely.
+    pub fn last_command(&self) -> Option<Command> {
+        self.last_command
+    }
+
+    fn decode(&mut self, byte: u8, frame: &mut LiveFrame) {
+        self.state = match self.state {
+            State::Header(mut header, n) => {
+                header[n] = byte;
+                if n < 3 {
+                    State::Header(header, n + 1)
+                } else {
+                    let [channel, command, hi, lo] = header;
+                    let len = u16::from_be_bytes([hi, lo]) as usize;
+                    self.begin(channel, command, len)
+                }
+            }
+            State::SystemId(mut id, n, len) => {
+                id[n] = byte;
+                if n == 0 {
+                    State::SystemId(id, 1, len)
+                } else {
+                    self.end(Command::SystemExclusive(u16::from_be_bytes(id)), len - 2)
+                }
+            }
+            State::Pixels { channel, len, received } => {
+                self.channels[received % 3] = byte;
+                if received % 3 == 2 {
+                    let [r, g, b] = self.channels;
+                    self.set_pixel(frame, channel, received / 3, Color { r, g, b });
+                }
+                if received + 1 < len {
+                    State::Pixels { channel, len, received: received + 1 }
+                } else {
+                    self.changed = true;
+                    self.end(Command::SetPixels, 0)
+                }
+            }
+            State::Skip(len) => if len > 1 { State::Skip(len - 1) } else { State::Header([0; 4], 0) }
+        }
+    }
+
+    fn begin(&mut self, channel: u8, command: u8, len: usize) -> State {
+        match command {
+            SET_PIXELS if len > 0 => State::Pixels { channel, len, received: 0 },
+            SET_PIXELS => self.end(Command::SetPixels, 0),
+            SYSTEM_EXCLUSIVE if len >= 2 => State::SystemId([0; 2], 0, len),
+            _ => self.end(Command::Other(command), len)
+        }
+    }
+
+    /// Skips the `len` bytes left of a message of `command`.
+    fn end(&mut self, command: Command, len: usize) -> State {
+        self.last_command = Some(command);
+        if len > 0 { State::Skip(len) } else { State::Header([0; 4], 0) }
+    }
+
+    fn set_pixel(&self, frame: &mut LiveFrame, channel: u8, pixel: usize, color: Color) {
+        let layout = self.layout;
+        let index = match channel as usize {
+            0 => pixel,
+            strip if strip <= layout.strips() && pixel < layout.strip_length(strip - 1) => {
+                layout.index(strip - 1, pixel)
+            }
+            _ => return
+        };
+        frame.set(index, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::{BLACK, RED, WHITE};
+    use proptest::prelude::*;
+
+    fn message(channel: u8, command: u8, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![channel, command];
+        bytes.extend((data.len() as u16).to_be_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    fn pixels(colors: &[Color]) -> Vec<u8> {
+        colors.iter().flat_map(|c| [c.r, c.g, c.b]).collect()
+    }
+
+    #[test]
+    fn channel_0_sets_all_leds() {
+        let layout = Layout::new(2, 3, true);
+        let mut decoder = OpcDecoder::new(layout);
+        let mut frame = LiveFrame::black(layout.num_leds());
+        decoder.push(&message(0, 0, &pixels(&[RED, WHITE, RED, WHITE, RED, WHITE, RED])), &mut frame);
+        assert!(decoder.take_changed());
+        assert!(frame.colors() == [RED, WHITE, RED, WHITE, RED, WHITE]);
+        assert!(!decoder.take_changed());
+    }
+
+    #[test]
+    fn channels_address_the_strips() {
+        let layout = Layout::new(3, 2, true);
+        let mut decoder = OpcDecoder::new(layout);
+        let mut frame = LiveFrame::new();
+        decoder.reset(&mut frame);
+        decoder.push(&message(2, 0, &pixels(&[RED, WHITE, WHITE])), &mut frame);
+        decoder.push(&message(4, 0, &pixels(&[WHITE])), &mut frame);
+        assert!(decoder.take_changed());
+        assert!(frame.colors() == [BLACK, BLACK, RED, WHITE, BLACK, BLACK]);
+    }
+
+    #[test]
+    fn other_messages_are_skipped() {
+        let mut decoder = OpcDecoder::new(Layout::new(1, 2, true));
+        let mut frame = LiveFrame::black(2);
+        decoder.push(&message(0, 255, &[0x00, 0x01, 0x02, 0x03]), &mut frame);
+        assert_eq!(decoder.last_command(), Some(Command::SystemExclusive(1)));
+        decoder.push(&message(0, 7, &[9; 5]), &mut frame);
+        assert_eq!(decoder.last_command(), Some(Command::Other(7)));
+        assert!(!decoder.take_changed());
+
+        decoder.push(&message(1, 0, &pixels(&[RED])), &mut frame);
+        assert_eq!(decoder.last_command(), Some(Command::SetPixels));
+        assert!(decoder.take_changed());
+        assert!(frame.colors() == [RED, BLACK]);
+    }
+
+    proptest! {
+        #[test]
+        fn messages_survive_any_split(split in 0usize..30) {
+            let mut bytes = message(0, 255, &[0x00, 0x01, 0xff]);
+            bytes.extend(message(1, 0, &pixels(&[RED, WHITE])));
+            bytes.extend(message(2, 0, &pixels(&[WHITE, RED])));
+            let mut decoder = OpcDecoder::new(Layout::new(2, 2, true));
+            let mut frame = LiveFrame::black(4);
+            decoder.push(&bytes[..split.min(bytes.len())], &mut frame);
+            decoder.push(&bytes[split.min(bytes.len())..], &mut frame);
+            prop_assert!(frame.colors() == [RED, WHITE, WHITE, RED]);
+        }
+    }
+}
@@ -0,0 +1,13 @@
+//! Getting the frames out to the strips
+
+use crate::ledstrip::LEDStrip;
+
+/// An output driver of the firmware
+///
+/// After `LEDStrip::process()` the data of the two chains is in `dump_0()` and
+/// `dump_1()`, in the format of the `protocol::Protocol` of the strips.  The
+/// driver sends it on, over SPI for the APA102s or with PIO state machines for
+/// the single wire LEDs.
+pub trait Output {
+    fn write(&mut self, led_strip: &LEDStrip);
+}
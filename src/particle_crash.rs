@@ -1,13 +1,13 @@
 use crate::{
     conf::*,
-    interface::Interface,
+    frameclock::Delta,
+    hardware::Hardware,
     led::WHITE,
     ledstrip::LEDStrip,
+    show::{Params, Show},
     sparks::Explosions
 };
 
-const ISTRIP_LENGTH: isize = STRIP_LENGTH as isize;
-
 
 enum Manor {
     Randomly,
@@ -41,12 +41,13 @@ impl BigParticle {
             return;
         }
 
-        let pos = ISTRIP_LENGTH * self.strip + self.current_position;
+        let layout = led_strip.layout();
+        let pos = layout.pos(self.strip, self.current_position);
         led_strip.set_led(pos, WHITE);
 
         self.current_position += 1;
 
-        if self.current_position > STRIP_LENGTH as isize {
+        if self.current_position > layout.length() as isize {
             self.deactivate();
         }
     }
@@ -79,35 +80,48 @@ impl SmallParticle {
             return;
         }
 
-        let pos = ISTRIP_LENGTH * self.strip + STRIP_LENGTH as isize - self.current_position;
+        let layout = led_strip.layout();
+        let pos = layout.pos(self.strip, layout.length() as isize - self.current_position);
         led_strip.set_led(pos, WHITE);
 
         self.current_position += 1;
 
-        if self.current_position > STRIP_LENGTH as isize {
+        if self.current_position > layout.length() as isize {
             self.deactivate();
         }
     }
 }
 
 pub struct ParticleCrash {
-    big_particles: [BigParticle; STRIP_NUM],
-    small_particles: [SmallParticle; STRIP_NUM],
+    big_particles: [BigParticle; MAX_STRIPS],
+    small_particles: [SmallParticle; MAX_STRIPS],
     explosions: Explosions,
-    step: usize
+    step: usize,
+    center_hue: f32,
+    manor: Manor
 }
 
 impl ParticleCrash {
     pub fn new() -> ParticleCrash {
+        ParticleCrash::with_manor(Manor::Randomly)
+    }
+
+    pub fn new_spiral() -> ParticleCrash {
+        ParticleCrash::with_manor(Manor::Spiral)
+    }
+
+    fn with_manor(manor: Manor) -> ParticleCrash {
         ParticleCrash {
-            big_particles: core::array::from_fn(|i| i).map(|strip| BigParticle::new(strip)),
-            small_particles: core::array::from_fn(|i| i).map(|strip| SmallParticle::new(strip)),
+            big_particles: core::array::from_fn(BigParticle::new),
+            small_particles: core::array::from_fn(SmallParticle::new),
             explosions: Explosions::new(),
-            step: 0
+            step: 0,
+            center_hue: 0.0,
+            manor
         }
     }
 
-    fn handle_colision(&mut self, strip: usize, explosion_hue: f32, interface: &mut Interface) {
+    fn handle_colision<H: Hardware>(&mut self, strip: usize, explosion_hue: f32, interface: &mut H) {
         let bp = &mut self.big_particles[strip];
         let sp = &mut self.small_particles[strip];
 
@@ -115,7 +129,8 @@ impl ParticleCrash {
             return
         }
 
-        if bp.current_position > ISTRIP_LENGTH - sp.current_position {
+        let length = interface.led_strip().layout().length() as isize;
+        if bp.current_position > length - sp.current_position {
             self.explosions.explode(
                 strip, bp.current_position as usize, explosion_hue, interface
             );
@@ -124,7 +139,7 @@ impl ParticleCrash {
         }
     }
 
-    fn randomly_activate_particles_on_strip(&mut self, strip: usize, interface: &mut Interface) {
+    fn randomly_activate_particles_on_strip<H: Hardware>(&mut self, strip: usize, interface: &mut H) {
         let no_crash_on_strip = self.explosions.no_explosion_on_strip(strip);
 
         let bp = &mut self.big_particles[strip];
@@ -151,18 +166,19 @@ impl ParticleCrash {
             bp.activate();
         }
     }
+}
 
-    pub fn show(&mut self, interface: &mut Interface) {
-        self.do_show(interface, Manor::Randomly);
+impl<H: Hardware> Show<H> for ParticleCrash {
+    fn name(&self) -> &'static str {
+        match self.manor {
+            Manor::Randomly => "particles",
+            Manor::Spiral => "particles_spiral"
+        }
     }
 
-    pub fn show_spiral(&mut self, interface: &mut Interface) {
+    fn init(&mut self, _interface: &mut H, _params: Params) {
+        self.center_hue = 0.0;
         self.step = 0;
-        self.do_show(interface, Manor::Spiral);
-    }
-
-    fn do_show(&mut self, interface: &mut Interface, manor: Manor) {
-        let mut center_hue = 0.0;
 
         self.explosions.reset();
         for bp in self.big_particles.iter_mut() {
@@ -172,49 +188,43 @@ impl ParticleCrash {
         for sp in self.small_particles.iter_mut() {
             sp.deactivate();
         }
-        loop {
-            interface.led_strip().black();
+    }
 
-            self.explosions.process(interface);
+    fn frame(&mut self, interface: &mut H, dt: Delta) {
+        interface.led_strip().black();
 
-            if !self.big_particles.iter().any(|p| p.is_active()) {
-                //self.step = 0;
-                center_hue = center_hue + 1.0 / 7.0;
-            }
+        self.explosions.process(interface, dt);
 
-            for strip in 0..STRIP_NUM {
-                self.big_particles[strip].process(&mut interface.led_strip());
-                self.small_particles[strip].process(&mut interface.led_strip());
+        if !self.big_particles.iter().any(|p| p.is_active()) {
+            self.center_hue += 1.0 / 7.0;
+        }
 
-                let hue = match manor {
-                    Manor::Randomly => interface.random().value(),
-                    Manor::Spiral => random_hue_around_given(center_hue, interface)
-                };
-                self.handle_colision(strip, hue, interface);
+        let strips = interface.led_strip().layout().strips();
+        for strip in 0..strips {
+            self.big_particles[strip].process(interface.led_strip());
+            self.small_particles[strip].process(interface.led_strip());
 
-                match manor {
-                    Manor::Randomly => self.randomly_activate_particles_on_strip(strip, interface),
-                    Manor::Spiral => self.activate_for_spiral(strip)
-                }
+            let hue = match self.manor {
+                Manor::Randomly => interface.random().value(),
+                Manor::Spiral => random_hue_around_given(self.center_hue, interface)
             };
+            self.handle_colision(strip, hue, interface);
 
-            self.step = (self.step+1) % (STRIP_NUM * 2);
-
-            if self.step == 0 {
-                center_hue = center_hue + 1.0 / 7.0;
+            match self.manor {
+                Manor::Randomly => self.randomly_activate_particles_on_strip(strip, interface),
+                Manor::Spiral => self.activate_for_spiral(strip)
             }
+        };
 
-            interface.write_spi();
-            if interface.do_next() {
-                interface.led_strip().black();
-                let _ = interface.led_off();
-                break;
-            }
+        self.step = (self.step+1) % (strips * 2);
+
+        if self.step == 0 {
+            self.center_hue += 1.0 / 7.0;
         }
     }
 }
 
 
-fn random_hue_around_given(center_hue: f32, interface: &mut Interface) -> f32 {
+fn random_hue_around_given<H: Hardware>(center_hue: f32, interface: &mut H) -> f32 {
     (interface.random().value() / 6.0 + center_hue) % 1.0
 }
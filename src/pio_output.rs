@@ -0,0 +1,135 @@
+//! WS2812 and SK6812 output with two state machines of PIO0, fed by DMA
+
+use fugit::MicrosDurationU64;
+use rp_pico::hal::{
+    pac,
+    dma::{Channel, CH0, CH1},
+    gpio::{self, Pin, FunctionPio0, PullDown},
+    pio::{PIOExt, PIOBuilder, Buffers, PinDir, ShiftDirection, Tx, SM0, SM1},
+    Timer,
+};
+
+use lumio::conf::{MAX_LEDS, PROTOCOL};
+use lumio::hardware::Instant;
+use lumio::ledstrip::LEDStrip;
+use lumio::output::Output;
+use lumio::protocol::Protocol;
+use lumio::ws2812::{self, T1, T2, T3};
+
+use crate::dma_chain::DmaChain;
+
+pub type Data0Pin = Pin<gpio::bank0::Gpio7, FunctionPio0, PullDown>;
+pub type Data1Pin = Pin<gpio::bank0::Gpio15, FunctionPio0, PullDown>;
+
+/// The most words of a chain, one per LED of the longer half.
+const CHAIN_WORDS: usize = MAX_LEDS.div_ceil(2);
+
+/// Chain 0 on the MOSI pin of SPI0, chain 1 on the one of SPI1.  Both send
+/// their front buffer at the same time while the next frame is rendered.
+pub struct PioOutput {
+    chain0: DmaChain<Channel<CH0>, Tx<(pac::PIO0, SM0)>, u32>,
+    chain1: DmaChain<Channel<CH1>, Tx<(pac::PIO0, SM1)>, u32>,
+    timer: Timer,
+    /// When the last frame is shifted out and latched.
+    done: Instant,
+    /// How long shifting out and latching a frame takes.
+    frame_time: MicrosDurationU64,
+    _pins: (Data0Pin, Data1Pin),
+}
+
+/// The program of the Raspberry Pi examples: every bit starts with `T1`
+/// cycles high, then `T2` cycles of the bit and ends with `T3` cycles low.
+fn program() -> pio::Program<{ pio::RP2040_MAX_PROGRAM_SIZE }> {
+    let side_set = pio::SideSet::new(false, 1, false);
+    let mut a = pio::Assembler::new_with_side_set(side_set);
+    let mut wrap_target = a.label();
+    let mut wrap_source = a.label();
+    let mut do_zero = a.label();
+
+    a.bind(&mut wrap_target);
+    a.out_with_delay_and_side_set(pio::OutDestination::X, 1, T3 - 1, 0);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::XIsZero, &mut do_zero, T1 - 1, 1);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::Always, &mut wrap_target, T2 - 1, 1);
+    a.bind(&mut do_zero);
+    a.nop_with_delay_and_side_set(T2 - 1, 0);
+    a.bind(&mut wrap_source);
+    a.assemble_with_wrap(wrap_source, wrap_target)
+}
+
+impl PioOutput {
+    /// The buffers take the length of the frames of `led_strip`.
+    pub fn new(
+        pio0: pac::PIO0,
+        pins: (Data0Pin, Data1Pin),
+        dma: (Channel<CH0>, Channel<CH1>),
+        led_strip: &LEDStrip,
+        resets: &mut pac::RESETS,
+        system_hz: u32,
+        timer: Timer,
+    ) -> PioOutput {
+        let (mut pio, sm0, sm1, _, _) = pio0.split(resets);
+        let installed = pio.install(&program()).unwrap();
+        // Both state machines run the same program, it is never uninstalled
+        let shared = unsafe { installed.share() };
+
+        let (int, frac) = ws2812::clock_divisor(system_hz);
+        let rgbw = PROTOCOL == Protocol::Sk6812Rgbw;
+        let bits = ws2812::bits(rgbw);
+        let builder = |program, pin: u8| {
+            PIOBuilder::from_program(program)
+                .side_set_pin_base(pin)
+                .out_shift_direction(ShiftDirection::Left)
+                .autopull(true)
+                .pull_threshold(bits)
+                .buffers(Buffers::OnlyTx)
+                .clock_divisor_fixed_point(int, frac)
+        };
+
+        let pin0 = pins.0.id().num;
+        let (mut sm0, _, tx0) = builder(installed, pin0).build(sm0);
+        sm0.set_pindirs([(pin0, PinDir::Output)]);
+        sm0.start();
+
+        let pin1 = pins.1.id().num;
+        let (mut sm1, _, tx1) = builder(shared, pin1).build(sm1);
+        sm1.set_pindirs([(pin1, PinDir::Output)]);
+        sm1.start();
+
+        let (len0, len1) = (led_strip.dump_0().len() / 4, led_strip.dump_1().len() / 4);
+        let buffers = [
+            &mut cortex_m::singleton!(: [u32; CHAIN_WORDS] = [0; CHAIN_WORDS]).unwrap()[..len0],
+            &mut cortex_m::singleton!(: [u32; CHAIN_WORDS] = [0; CHAIN_WORDS]).unwrap()[..len0],
+            &mut cortex_m::singleton!(: [u32; CHAIN_WORDS] = [0; CHAIN_WORDS]).unwrap()[..len1],
+            &mut cortex_m::singleton!(: [u32; CHAIN_WORDS] = [0; CHAIN_WORDS]).unwrap()[..len1],
+        ];
+        let [front0, back0, front1, back1] = buffers;
+        let frame_us = ws2812::frame_us(len0.max(len1), rgbw);
+
+        PioOutput {
+            chain0: DmaChain::new(dma.0, tx0, front0, back0),
+            chain1: DmaChain::new(dma.1, tx1, front1, back1),
+            timer,
+            done: timer.get_counter(),
+            frame_time: MicrosDurationU64::micros(frame_us as u64),
+            _pins: pins,
+        }
+    }
+}
+
+impl Output for PioOutput {
+    fn write(&mut self, led_strip: &LEDStrip) {
+        for (word, data) in self.chain0.back().iter_mut().zip(ws2812::words(led_strip.dump_0())) {
+            *word = data;
+        }
+        for (word, data) in self.chain1.back().iter_mut().zip(ws2812::words(led_strip.dump_1())) {
+            *word = data;
+        }
+
+        // The LEDs only take new data after the line was low long enough,
+        // the DMA is through by then
+        while self.timer.get_counter() < self.done {}
+        self.chain0.send();
+        self.chain1.send();
+        self.done = self.timer.get_counter() + self.frame_time;
+    }
+}
@@ -3,9 +3,10 @@ use core::f32::consts::PI;
 use libm::{fabsf, sqrtf};
 
 use crate::{
-    button::ButtonState, conf::*, interface::Interface, led::{Color, BLACK, YELLOW}, sparks::Explosions
+    button::ButtonState, frameclock::Delta, hardware::Hardware, layout::Layout, led::{Color, BLACK, YELLOW}, show::{Params, Show}, sparks::Explosions
 };
 
+/// The time step of the orbits per frame.
 const DELTA_T: f32 = 10.0;
 
 pub struct Planet {
@@ -36,8 +37,8 @@ impl Planet {
         self.is_active = true;
     }
 
-    fn step_rad(&mut self, n: usize) {
-        let delta_t = DELTA_T / n as f32;
+    fn step_rad(&mut self, n: usize, dt: Delta) {
+        let delta_t = DELTA_T * dt.frames() / n as f32;
         for _ in 0..n {
             let dd_rad = self.p_rad * self.d_phi * self.d_phi - 1.0 / (self.p_rad * self.p_rad);
             self.d_rad += dd_rad * delta_t;
@@ -45,8 +46,8 @@ impl Planet {
         }
     }
 
-    fn step_phi(&mut self, n: usize) {
-        let delta_t = DELTA_T / n as f32;
+    fn step_phi(&mut self, n: usize, dt: Delta) {
+        let delta_t = DELTA_T * dt.frames() / n as f32;
         for _ in 0..n {
         let dd_phi = - 2.0 * self.d_rad * self.d_phi / self.p_rad;
         self.d_phi += dd_phi * delta_t;
@@ -54,36 +55,36 @@ impl Planet {
         }
     }
 
-    fn position(&self) -> (isize, isize) {
+    fn position(&self, layout: Layout) -> (isize, isize) {
         let p_phi = if self.p_phi < 0.0 {
             2.0 * PI + self.p_phi
         } else if self.p_phi > 2.0 * PI {
             self.p_phi % (2.0 * PI)
         } else {
             self.p_phi
-        } / (2.0*PI) * STRIP_NUM as f32;
+        } / (2.0*PI) * layout.strips() as f32;
 
         let p_rad = self.p_rad;
         (p_phi as isize, p_rad as isize)
     }
 
-    fn process(&mut self) -> (isize, isize) {
+    fn process(&mut self, layout: Layout, dt: Delta) -> (isize, isize) {
         if self.p_rad <= 0.0 {
             return (0, 0)
         }
 
         let step_num_phi = fabsf(10000.0 * self.d_phi);
         let step_num_rad = fabsf(100.0 * self.d_rad);
-        let step_num = (step_num_rad + step_num_phi).max(1.0) as usize;
-        self.step_rad(step_num);
-        self.step_phi(step_num);
+        let step_num = ((step_num_rad + step_num_phi) * dt.frames()).max(1.0) as usize;
+        self.step_rad(step_num, dt);
+        self.step_phi(step_num, dt);
 
 
         if self.p_phi < 0.0 {
             self.p_phi += 2.0 * PI;
         }
 
-        self.position()
+        self.position(layout)
     }
 
     fn is_active(&self) -> bool { self.is_active }
@@ -93,92 +94,99 @@ impl Planet {
 
 
 pub struct PlanetShow {
+    planets: [Planet; NUM_PLANETS],
     explosions: Explosions,
+    with_collisions: bool,
 }
 
 const NUM_PLANETS: usize = 10;
 
 impl PlanetShow {
     pub fn new() -> PlanetShow {
-        PlanetShow { explosions: Explosions::new() }
-    }
-
-    pub fn show(&mut self, interface: &mut Interface) {
-        let mut planets = initialize_planets(interface);
-        let mut with_collisions = true;
-
-        interface.led_on();
-
-        interface.led_strip().black();
-
-        loop {
-            make_sun_flicker(interface);
-            for n in 0..STRIP_NUM {
-                make_sun_corona_on_strip(n, interface);
-                make_rest_of_sky_black(n, interface);
-            }
-
-            if with_collisions {
-                self.handle_colisions(&mut planets, interface);
-            }
-
-            self.process_planets(&mut planets, interface);
-            self.explosions.process(interface);
-
-            if interface.button_state() == ButtonState::LongPressed {
-                with_collisions = !with_collisions;
-                if with_collisions {
-                    let _ = interface.led_on();
-                } else {
-                    let _ = interface.led_off();
-                }
-            }
-
-            interface.write_spi();
-
-            if interface.do_next() {
-                interface.led_strip().black();
-                let _ = interface.led_off();
-                break;
-            }
+        PlanetShow {
+            planets: core::array::from_fn(|_i| Planet::new(0.0, 0.0, 0.0, 0.0, BLACK)),
+            explosions: Explosions::new(),
+            with_collisions: true,
         }
     }
 
-    fn process_planets(&mut self, planets: &mut [Planet; NUM_PLANETS], interface: &mut Interface) {
-        for planet in planets.iter_mut() {
+    fn process_planets<H: Hardware>(&mut self, interface: &mut H, dt: Delta) {
+        let layout = interface.led_strip().layout();
+        for planet in self.planets.iter_mut() {
             if !planet.is_active() {
-                let (strip, _) = planet.position();
+                let (strip, _) = planet.position(layout);
                 if self.explosions.no_explosion_on_strip(strip as usize) {
                     planet.reset_phi(interface.random().value() * 2.0 * PI);
                 }
             }
-            let (strip_num, pos) = planet.process();
-            if pos < STRIP_LENGTH as isize && pos > 0 && planet.is_active() {
-                interface.led_strip().set_led(flat_pos(strip_num, pos), planet.color);
+            let (strip_num, pos) = planet.process(layout, dt);
+            if pos < layout.length() as isize && pos > 0 && planet.is_active() {
+                interface.led_strip().set_led(layout.pos(strip_num, pos), planet.color);
             }
         }
     }
 
-    fn handle_colisions(&mut self, planets: &mut [Planet; NUM_PLANETS], interface: &mut Interface) {
-        let positions: [(isize, isize); NUM_PLANETS] = core::array::from_fn(|i| planets[i].position());
+    fn handle_colisions<H: Hardware>(&mut self, interface: &mut H) {
+        let layout = interface.led_strip().layout();
+        let positions: [(isize, isize); NUM_PLANETS] = core::array::from_fn(|i| self.planets[i].position(layout));
 
         for i in 0..positions.len() {
             let (sn1, p1) = positions[i];
-            for j in i+1..NUM_PLANETS {
-                let (sn2, p2) = positions[j];
+            for (j, &(sn2, p2)) in positions.iter().enumerate().skip(i+1) {
                 if p1 == p2 && sn1 == sn2 {
-                    interface.led_strip().set_led(flat_pos(sn1, p1), BLACK);
+                    interface.led_strip().set_led(layout.pos(sn1, p1), BLACK);
                     let hue1 = i as f32 / NUM_PLANETS as f32;
                     self.explosions.explode(sn1 as usize, p1 as usize, hue1, interface);
-                    planets[i].deactivate();
-                    planets[j].deactivate();
+                    self.planets[i].deactivate();
+                    self.planets[j].deactivate();
                 }
             }
         }
     }
 }
 
-fn initialize_planets(interface: &mut Interface) -> [Planet; NUM_PLANETS] {
+impl<H: Hardware> Show<H> for PlanetShow {
+    fn name(&self) -> &'static str {
+        "planets"
+    }
+
+    fn init(&mut self, interface: &mut H, _params: Params) {
+        self.planets = initialize_planets(interface);
+        self.with_collisions = true;
+
+        interface.led_on();
+
+        interface.led_strip().black();
+    }
+
+    fn frame(&mut self, interface: &mut H, dt: Delta) {
+        make_sun_flicker(interface);
+        for n in 0..interface.led_strip().layout().strips() {
+            make_sun_corona_on_strip(n, interface);
+            make_rest_of_sky_black(n, interface);
+        }
+
+        if self.with_collisions {
+            self.handle_colisions(interface);
+        }
+
+        self.process_planets(interface, dt);
+        self.explosions.process(interface, dt);
+    }
+
+    fn on_button(&mut self, state: ButtonState, interface: &mut H) {
+        if state == ButtonState::LongPressed {
+            self.with_collisions = !self.with_collisions;
+            if self.with_collisions {
+                interface.led_on();
+            } else {
+                interface.led_off();
+            }
+        }
+    }
+}
+
+fn initialize_planets<H: Hardware>(interface: &mut H) -> [Planet; NUM_PLANETS] {
     let mut a = 1.0;
     let mut hue = 0.0;
     let planets: [Planet; NUM_PLANETS] = core::array::from_fn(|_i| {
@@ -201,30 +209,71 @@ fn initialize_planets(interface: &mut Interface) -> [Planet; NUM_PLANETS] {
     planets
 }
 
-fn make_rest_of_sky_black(n: usize, interface: &mut Interface) {
-    (0..STRIP_LENGTH).for_each(|pos| {
-        let led = interface.led_strip().led_mut(n * STRIP_LENGTH + pos);
+fn make_rest_of_sky_black<H: Hardware>(n: usize, interface: &mut H) {
+    let layout = interface.led_strip().layout();
+    (0..layout.length()).for_each(|pos| {
+        let led = interface.led_strip().led_mut(layout.index(n, pos));
         if !led.is_flickering() {
             led.set_color(BLACK);
         }
     });
 }
 
-fn make_sun_corona_on_strip(n: usize, interface: &mut Interface) {
-    let pos = n * STRIP_LENGTH + 1;
+fn make_sun_corona_on_strip<H: Hardware>(n: usize, interface: &mut H) {
+    let pos = interface.led_strip().layout().index(n, 1);
     if interface.random().value8() < 8 && interface.led_strip().led(pos).is_black() {
         interface.led_strip().set_led(pos as isize, YELLOW);
         interface.led_strip().led_mut(pos).set_target_flickering(BLACK, 96, 255);
     }
 }
 
-fn make_sun_flicker(interface: &mut Interface) {
-    for n in 0..STRIP_NUM {
-        let pos = n * STRIP_LENGTH;
+fn make_sun_flicker<H: Hardware>(interface: &mut H) {
+    let layout = interface.led_strip().layout();
+    for n in 0..layout.strips() {
+        let pos = layout.index(n, 0);
         interface.led_strip().led_mut(pos).set_color_flickering(YELLOW, 192);
     }
 }
 
-fn flat_pos(strip_num: isize, pos: isize) -> isize {
-    strip_num * STRIP_LENGTH as isize + pos
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn circular_orbit_keeps_its_radius(rad in 5.0f32..45.0, phi in 0.0f32..2.0 * PI) {
+            let mut planet = Planet::new_vis_viva(rad, 1.0, phi, 1.0, YELLOW);
+            for _ in 0..100 {
+                planet.process(Layout::FLOWER, Delta::ONE);
+            }
+            prop_assert!(fabsf(planet.p_rad - rad) < 0.05 * rad);
+        }
+
+        #[test]
+        fn position_is_on_a_strip(rad in 5.0f32..45.0, a in 1.0f32..2.5, phi in 0.0f32..2.0 * PI, direction in prop_oneof![Just(-1.0f32), Just(1.0f32)]) {
+            let mut planet = Planet::new_vis_viva(rad, a, phi, direction, YELLOW);
+            for _ in 0..100 {
+                let (strip, _) = planet.process(Layout::FLOWER, Delta::ONE);
+                prop_assert!((0..Layout::FLOWER.strips() as isize).contains(&strip));
+            }
+        }
+    }
+
+    #[test]
+    fn direction_sets_the_sense_of_rotation() {
+        let mut forward = Planet::new_vis_viva(20.0, 1.0, PI, 1.0, YELLOW);
+        let mut backward = Planet::new_vis_viva(20.0, 1.0, PI, -1.0, YELLOW);
+        forward.process(Layout::FLOWER, Delta::ONE);
+        backward.process(Layout::FLOWER, Delta::ONE);
+        assert!(forward.p_phi > PI);
+        assert!(backward.p_phi < PI);
+    }
+
+    #[test]
+    fn fallen_planet_stays_in_the_center() {
+        let mut planet = Planet::new(0.0, 1.0, 0.0, 0.1, YELLOW);
+        assert_eq!(planet.process(Layout::FLOWER, Delta::ONE), (0, 0));
+        assert_eq!(planet.p_phi, 1.0);
+    }
 }
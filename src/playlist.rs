@@ -0,0 +1,329 @@
+//! The order in which the shows are run
+//!
+//! A `Playlist` is a list of entries, each naming a show of the `Programme`
+//! and the `Params` the show starts with, e.g. its colours, so the same show
+//! can run with different settings.  The entries are played in order,
+//! shuffled or drawn at random by their weight.  Shows that ran recently can
+//! be held back.  Each entry runs for its own duration, by default
+//! `AUTO_SHOW_DELAY`, or until its show has finished, and starts with its own
+//! `Transition` out of the show before, by default `TRANSITION`.
+
+use fugit::MicrosDurationU64;
+
+use crate::conf::{AUTO_SHOW_DELAY, MAX_PLAYLIST, MAX_NO_REPEAT, TRANSITION};
+use crate::led::Color;
+use crate::show::Params;
+use crate::transition::Transition;
+use crate::random::Random;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Order {
+    Sequential,
+    /// Every entry once per round, the rounds in random order.
+    Shuffled,
+    /// Entries drawn at random, in proportion to their weight.
+    Weighted
+}
+
+/// How long an entry runs while the shows change on their own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Duration {
+    For(MicrosDurationU64),
+    /// Until `Show::is_finished()`.
+    UntilFinished
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Entry {
+    show: &'static str,
+    params: Params,
+    weight: u8,
+    duration: Duration,
+    transition: Transition
+}
+
+impl Entry {
+    /// The show named `show` with its own settings, with weight 1, for
+    /// `AUTO_SHOW_DELAY`.
+    pub const fn new(show: &'static str) -> Entry {
+        Entry {
+            show,
+            params: Params::new(),
+            weight: 1,
+            duration: Duration::For(AUTO_SHOW_DELAY),
+            transition: TRANSITION
+        }
+    }
+
+    pub const fn with_params(mut self, params: Params) -> Entry {
+        self.params = params;
+        self
+    }
+
+    /// See `Params::with_colors()`.
+    pub const fn with_colors(mut self, background: Color, foreground: Color) -> Entry {
+        self.params = self.params.with_colors(background, foreground);
+        self
+    }
+
+    /// How often the entry is drawn with `Order::Weighted`, 0 never.
+    pub const fn with_weight(mut self, weight: u8) -> Entry {
+        self.weight = weight;
+        self
+    }
+
+    pub const fn with_duration(mut self, duration: MicrosDurationU64) -> Entry {
+        self.duration = Duration::For(duration);
+        self
+    }
+
+    /// Runs the show until it has finished, e.g. a snake run.
+    pub const fn until_finished(mut self) -> Entry {
+        self.duration = Duration::UntilFinished;
+        self
+    }
+
+    /// How the show takes over from the one before.
+    pub const fn with_transition(mut self, transition: Transition) -> Entry {
+        self.transition = transition;
+        self
+    }
+
+    pub const fn show(&self) -> &'static str {
+        self.show
+    }
+
+    pub const fn params(&self) -> Params {
+        self.params
+    }
+
+    pub const fn weight(&self) -> u8 {
+        self.weight
+    }
+
+    pub const fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub const fn transition(&self) -> Transition {
+        self.transition
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Playlist {
+    entries: &'static [Entry],
+    order: Order,
+    no_repeat: usize,
+    /// The entries of the current round of a shuffled playlist.
+    deck: [u8; MAX_PLAYLIST],
+    /// The next entry to play, in the order or the deck.
+    position: usize,
+    /// The last entries played, the latest first.
+    history: [Option<u8>; MAX_NO_REPEAT]
+}
+
+impl Playlist {
+    /// Plays `entries` in order.
+    pub const fn new(entries: &'static [Entry]) -> Playlist {
+        assert!(!entries.is_empty() && entries.len() <= MAX_PLAYLIST, "playlist exceeds MAX_PLAYLIST");
+        Playlist {
+            entries,
+            order: Order::Sequential,
+            no_repeat: 0,
+            deck: [0; MAX_PLAYLIST],
+            position: entries.len(),
+            history: [None; MAX_NO_REPEAT]
+        }
+    }
+
+    pub const fn with_order(mut self, order: Order) -> Playlist {
+        self.order = order;
+        self
+    }
+
+    /// Holds back the shows of the last `shows` entries played, as far as
+    /// there are others to choose from.
+    pub const fn without_repeats(mut self, shows: usize) -> Playlist {
+        assert!(shows <= MAX_NO_REPEAT, "no_repeat exceeds MAX_NO_REPEAT");
+        self.no_repeat = shows;
+        self
+    }
+
+    pub fn entries(&self) -> &'static [Entry] {
+        self.entries
+    }
+
+    pub fn order(&self) -> Order {
+        self.order
+    }
+
+    /// The first entry of `show`, if there is one.
+    pub fn entry(&self, show: &str) -> Option<Entry> {
+        self.entries.iter().find(|entry| entry.show == show).copied()
+    }
+
+    /// Picks the entry to play next.
+    pub fn next(&mut self, random: &mut Random) -> Entry {
+        let index = match self.order {
+            Order::Sequential => {
+                if self.position >= self.entries.len() {
+                    self.position = 0;
+                }
+                self.position += 1;
+                self.position - 1
+            }
+            Order::Shuffled => self.draw_from_deck(random),
+            Order::Weighted => self.draw_weighted(random)
+        };
+        self.history.copy_within(..MAX_NO_REPEAT - 1, 1);
+        self.history[0] = Some(index as u8);
+        self.entries[index]
+    }
+
+    /// The entry picked last.
+    pub fn current(&self) -> Option<Entry> {
+        self.history[0].map(|index| self.entries[index as usize])
+    }
+
+    /// Steps back to the entry picked before the last one, as far as the
+    /// history reaches.
+    pub fn back(&mut self) -> Option<Entry> {
+        let previous = self.history[1]? as usize;
+        self.history.copy_within(1.., 0);
+        self.history[MAX_NO_REPEAT - 1] = None;
+        if self.order == Order::Sequential {
+            self.position = previous + 1;
+        }
+        Some(self.entries[previous])
+    }
+
+    /// Whether the show of entry `index` ran within the last `no_repeat`
+    /// entries.
+    fn is_recent(&self, index: usize) -> bool {
+        let show = self.entries[index].show;
+        self.history[..self.no_repeat].iter().flatten().any(|&i| self.entries[i as usize].show == show)
+    }
+
+    fn draw_from_deck(&mut self, random: &mut Random) -> usize {
+        let len = self.entries.len();
+        if self.position >= len {
+            for i in 0..len {
+                self.deck[i] = i as u8;
+            }
+            for i in (1..len).rev() {
+                self.deck.swap(i, random.value32(i as u32 + 1) as usize);
+            }
+            self.position = 0;
+        }
+
+        // Pull a later entry forward if this one ran recently
+        let fresh = (self.position..len).find(|&i| !self.is_recent(self.deck[i] as usize));
+        if let Some(fresh) = fresh {
+            self.deck.swap(self.position, fresh);
+        }
+        self.position += 1;
+        self.deck[self.position - 1] as usize
+    }
+
+    fn draw_weighted(&mut self, random: &mut Random) -> usize {
+        let len = self.entries.len();
+        let avoid_recent = (0..len).any(|i| self.is_candidate(i, true));
+        let weight = |i: usize| self.entries[i].weight as u32;
+
+        let total: u32 = (0..len).filter(|&i| self.is_candidate(i, avoid_recent)).map(weight).sum();
+        let mut ticket = random.value32(total);
+        for i in (0..len).filter(|&i| self.is_candidate(i, avoid_recent)) {
+            if ticket < weight(i) {
+                return i;
+            }
+            ticket -= weight(i);
+        }
+        0
+    }
+
+    fn is_candidate(&self, index: usize, avoid_recent: bool) -> bool {
+        self.entries[index].weight > 0 && !(avoid_recent && self.is_recent(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const ENTRIES: &[Entry] = &[
+        Entry::new("snow"),
+        Entry::new("sine"),
+        Entry::new("fireworks").with_weight(3),
+        Entry::new("snake").with_weight(0),
+        Entry::new("snow")
+    ];
+
+    fn play(playlist: &mut Playlist, seed: u64, n: usize) -> Vec<&'static str> {
+        let mut random = Random::new(seed);
+        (0..n).map(|_| playlist.next(&mut random).show()).collect()
+    }
+
+    #[test]
+    fn sequential_plays_in_order() {
+        let mut playlist = Playlist::new(ENTRIES);
+        assert_eq!(
+            play(&mut playlist, 1, 6),
+            ["snow", "sine", "fireworks", "snake", "snow", "snow"]
+        );
+    }
+
+    #[test]
+    fn weight_zero_is_never_drawn() {
+        let mut playlist = Playlist::new(ENTRIES).with_order(Order::Weighted);
+        let shows = play(&mut playlist, 7, 1000);
+        assert!(!shows.contains(&"snake"));
+        let fireworks = shows.iter().filter(|&&s| s == "fireworks").count();
+        let sine = shows.iter().filter(|&&s| s == "sine").count();
+        assert!(fireworks > 2 * sine, "{fireworks} {sine}");
+    }
+
+    #[test]
+    fn back_steps_through_the_history() {
+        let mut playlist = Playlist::new(ENTRIES);
+        assert_eq!(playlist.back(), None);
+        play(&mut playlist, 1, 3);
+        assert_eq!(playlist.current().map(|entry| entry.show()), Some("fireworks"));
+        assert_eq!(playlist.back().map(|entry| entry.show()), Some("sine"));
+        assert_eq!(playlist.back().map(|entry| entry.show()), Some("snow"));
+        assert_eq!(playlist.back(), None);
+        assert_eq!(play(&mut playlist, 1, 2), ["sine", "fireworks"]);
+    }
+
+    proptest! {
+        #[test]
+        fn shuffled_rounds_play_every_entry(seed: u64) {
+            let mut playlist = Playlist::new(ENTRIES).with_order(Order::Shuffled);
+            for _ in 0..3 {
+                let mut round = play(&mut playlist, seed, ENTRIES.len());
+                round.sort();
+                prop_assert_eq!(round, ["fireworks", "sine", "snake", "snow", "snow"]);
+            }
+        }
+
+        #[test]
+        fn recent_shows_are_held_back(seed: u64) {
+            let mut playlist = Playlist::new(ENTRIES).with_order(Order::Weighted).without_repeats(1);
+            let shows = play(&mut playlist, seed, 200);
+            for pair in shows.windows(2) {
+                prop_assert_ne!(pair[0], pair[1]);
+            }
+        }
+
+        #[test]
+        fn shuffled_rounds_hold_back_the_end_of_the_last_one(seed: u64) {
+            const DISTINCT: &[Entry] = &[Entry::new("a"), Entry::new("b"), Entry::new("c"), Entry::new("d")];
+            let mut playlist = Playlist::new(DISTINCT).with_order(Order::Shuffled).without_repeats(2);
+            let shows = play(&mut playlist, seed, 40);
+            for window in shows.windows(3) {
+                prop_assert!(window[2] != window[1] && window[2] != window[0]);
+            }
+        }
+    }
+}
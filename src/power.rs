@@ -0,0 +1,269 @@
+//! Limiting the current drawn by the LEDs
+//!
+//! The chain of physical LEDs is fed by a number of power feeds, each taking an
+//! equal share of the chain.  With two feeds these are the halves on SPI0 and
+//! SPI1.  Every frame the current of each feed is estimated from the colours
+//! about to be sent.  If a feed would draw more than its budget, the whole
+//! frame is dimmed uniformly until it fits.  The dimming follows at once but
+//! wears off slowly, so bright shows don't pump.
+
+use core::fmt;
+
+use crate::conf::MAX_FEEDS;
+
+/// Scale factor of 1.0 for the dimming of the channels.
+pub const UNITY: u32 = 1 << 16;
+/// How much the dimming may wear off per frame.
+const RECOVERY: u32 = UNITY / 128;
+/// Full intensity of a 16 bit channel.
+const FULL: u64 = u16::MAX as u64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PowerBudget {
+    feed_ma: u32,
+    feeds: usize,
+    channel_ma: [u32; 3],
+    idle_ma: u32
+}
+
+impl PowerBudget {
+    /// No limit at all, nothing is estimated.
+    pub const fn unlimited() -> PowerBudget {
+        PowerBudget { feed_ma: u32::MAX, feeds: 1, channel_ma: [20; 3], idle_ma: 1 }
+    }
+
+    /// `feeds` feeds of `feed_ma` each, with the currents of APA102s.
+    pub const fn new(feed_ma: u32, feeds: usize) -> PowerBudget {
+        assert!(feeds > 0 && feeds <= MAX_FEEDS, "feeds exceed MAX_FEEDS");
+        PowerBudget { feed_ma, feeds, ..PowerBudget::unlimited() }
+    }
+
+    /// The current of the red, green and blue channel of a LED at full
+    /// intensity.
+    pub const fn with_channel_current(mut self, ma: [u32; 3]) -> PowerBudget {
+        self.channel_ma = ma;
+        self
+    }
+
+    /// The current of a LED that is off.
+    pub const fn with_idle_current(mut self, ma: u32) -> PowerBudget {
+        self.idle_ma = ma;
+        self
+    }
+
+    pub const fn is_unlimited(&self) -> bool {
+        self.feed_ma == u32::MAX
+    }
+
+    pub const fn feeds(&self) -> usize {
+        self.feeds
+    }
+
+    /// The feed of physical LED `led` out of `leds`.
+    pub fn feed(&self, led: usize, leds: usize) -> usize {
+        led * self.feeds / leds
+    }
+}
+
+/// A change of the power limiting, for the log.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerReport {
+    /// Dimmed to `percent`, `feed` asked for `wanted_ma`.
+    Limiting { percent: u8, feed: usize, wanted_ma: u32 },
+    WithinBudget
+}
+
+impl fmt::Display for PowerReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowerReport::Limiting { percent, feed, wanted_ma } => {
+                write!(f, "power limited to {percent} %, feed {feed} wants {wanted_ma} mA")
+            }
+            PowerReport::WithinBudget => write!(f, "power within budget"),
+        }
+    }
+}
+
+/// Estimates the current of each frame and keeps track of the dimming.
+pub struct PowerLimiter {
+    budget: PowerBudget,
+    /// Load of each feed, channel intensity times mA.
+    load: [u64; MAX_FEEDS],
+    leds: [u32; MAX_FEEDS],
+    feed_current: [u32; MAX_FEEDS],
+    scale: u32,
+    report: Option<PowerReport>,
+    reported_limiting: bool
+}
+
+impl PowerLimiter {
+    pub fn new(budget: PowerBudget) -> PowerLimiter {
+        PowerLimiter {
+            budget,
+            load: [0; MAX_FEEDS],
+            leds: [0; MAX_FEEDS],
+            feed_current: [0; MAX_FEEDS],
+            scale: UNITY,
+            report: None,
+            reported_limiting: false
+        }
+    }
+
+    pub fn budget(&self) -> PowerBudget {
+        self.budget
+    }
+
+    /// Starts the estimate of a frame.
+    pub fn begin(&mut self) {
+        self.load = [0; MAX_FEEDS];
+        self.leds = [0; MAX_FEEDS];
+    }
+
+    /// Adds the 16 bit intensities (red, green, blue) of a LED to a feed.
+    pub fn add(&mut self, feed: usize, rgb: [u16; 3]) {
+        for (c, ma) in rgb.iter().zip(self.budget.channel_ma) {
+            self.load[feed] += *c as u64 * ma as u64;
+        }
+        self.leds[feed] += 1;
+    }
+
+    /// Ends the estimate of a frame at master `brightness` and updates the
+    /// dimming.
+    pub fn end(&mut self, brightness: u8) {
+        let mut target = UNITY;
+        let mut worst = (0, 0);
+        for feed in 0..self.budget.feeds {
+            let idle = self.leds[feed] * self.budget.idle_ma;
+            let lit = (self.load[feed] * brightness as u64 / (FULL * 255)) as u32;
+            self.feed_current[feed] = idle + lit;
+
+            if lit > 0 && idle + lit > self.budget.feed_ma {
+                let spare = self.budget.feed_ma.saturating_sub(idle);
+                let scale = (spare as u64 * UNITY as u64 / lit as u64) as u32;
+                if scale < target {
+                    target = scale;
+                    worst = (feed, idle + lit);
+                }
+            }
+        }
+
+        self.scale = if target < self.scale { target } else { target.min(self.scale + RECOVERY) };
+
+        let limiting = self.scale < UNITY;
+        if limiting && !self.reported_limiting {
+            let percent = (self.scale * 100 / UNITY) as u8;
+            self.report = Some(PowerReport::Limiting { percent, feed: worst.0, wanted_ma: worst.1 });
+        } else if !limiting && self.reported_limiting {
+            self.report = Some(PowerReport::WithinBudget);
+        }
+        self.reported_limiting = limiting;
+    }
+
+    /// The dimming of the channels, `UNITY` if within budget.
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Dims 16 bit intensities by the current scale.
+    pub fn apply(&self, rgb: [u16; 3]) -> [u16; 3] {
+        rgb.map(|c| ((c as u32 * self.scale) >> 16) as u16)
+    }
+
+    /// The estimated current of each feed before dimming.
+    pub fn feed_current(&self) -> &[u32] {
+        &self.feed_current[..self.budget.feeds]
+    }
+
+    pub fn take_report(&mut self) -> Option<PowerReport> {
+        self.report.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const WHITE: [u16; 3] = [u16::MAX; 3];
+
+    fn frame(limiter: &mut PowerLimiter, feeds: &[(usize, [u16; 3])]) {
+        limiter.begin();
+        for &(feed, rgb) in feeds {
+            limiter.add(feed, rgb);
+        }
+        limiter.end(255);
+    }
+
+    #[test]
+    fn feeds_split_the_chain() {
+        let budget = PowerBudget::new(1000, 2);
+        assert_eq!(budget.feed(0, 10), 0);
+        assert_eq!(budget.feed(4, 10), 0);
+        assert_eq!(budget.feed(5, 10), 1);
+        assert_eq!(budget.feed(9, 10), 1);
+        assert_eq!(PowerBudget::new(1000, 3).feed(9, 10), 2);
+    }
+
+    #[test]
+    fn within_budget_changes_nothing() {
+        let mut limiter = PowerLimiter::new(PowerBudget::new(1000, 1));
+        frame(&mut limiter, &[(0, WHITE); 10]);
+        assert_eq!(limiter.feed_current(), [610]);
+        assert_eq!(limiter.scale(), UNITY);
+        assert_eq!(limiter.apply([1234, 0, 65535]), [1234, 0, 65535]);
+        assert_eq!(limiter.take_report(), None);
+    }
+
+    #[test]
+    fn over_budget_dims_at_once_and_recovers_slowly() {
+        let mut limiter = PowerLimiter::new(
+            PowerBudget::new(310, 2).with_channel_current([100; 3]).with_idle_current(0)
+        );
+        // Feed 1 wants 600 mA, twice its budget
+        frame(&mut limiter, &[(0, [u16::MAX, 0, 0]), (1, WHITE), (1, WHITE)]);
+        assert_eq!(limiter.scale(), UNITY * 310 / 600);
+        assert_eq!(
+            limiter.take_report(),
+            Some(PowerReport::Limiting { percent: 51, feed: 1, wanted_ma: 600 })
+        );
+
+        frame(&mut limiter, &[]);
+        assert_eq!(limiter.scale(), UNITY * 310 / 600 + RECOVERY);
+        assert_eq!(limiter.take_report(), None);
+
+        for _ in 0..128 {
+            frame(&mut limiter, &[]);
+        }
+        assert_eq!(limiter.scale(), UNITY);
+        assert_eq!(limiter.take_report(), Some(PowerReport::WithinBudget));
+    }
+
+    #[test]
+    fn master_brightness_counts() {
+        let mut limiter = PowerLimiter::new(PowerBudget::new(1000, 1).with_idle_current(0));
+        limiter.begin();
+        limiter.add(0, WHITE);
+        limiter.end(51);
+        assert_eq!(limiter.feed_current(), [12]);
+    }
+
+    proptest! {
+        #[test]
+        fn dimmed_frames_fit_the_budget(
+            leds in prop::collection::vec((0usize..3, any::<[u16; 3]>()), 0..200),
+            feed_ma in 100u32..5000
+        ) {
+            let budget = PowerBudget::new(feed_ma, 3);
+            let mut limiter = PowerLimiter::new(budget);
+            frame(&mut limiter, &leds);
+
+            let dimmed: Vec<_> = leds.iter().map(|&(feed, rgb)| (feed, limiter.apply(rgb))).collect();
+            let mut check = PowerLimiter::new(budget);
+            frame(&mut check, &dimmed);
+            for (feed, current) in check.feed_current().iter().enumerate() {
+                let idle = leds.iter().filter(|(f, _)| *f == feed).count() as u32;
+                prop_assert!(*current <= feed_ma.max(idle));
+            }
+        }
+    }
+}
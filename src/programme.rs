@@ -0,0 +1,89 @@
+use crate::{
+    fire::Fire,
+    hardware::Hardware,
+    led::{WHITE, YELLOW, DARK_BLUE, DARK_GREEN},
+    particle_crash::ParticleCrash,
+    planets::PlanetShow,
+    show::Show,
+    sine::{SeaWave, SineShow},
+    snake::SnakeShow,
+    snow::Snow,
+    sparks::{FireWorks, SparkFall, SnowSparks},
+    spiral::HueSpiral,
+    stars::Stars,
+};
+
+pub const SHOW_NUM: usize = 18;
+
+/// All the shows of the installation in the order they are run.
+pub struct Programme {
+    hue_spiral_lift: HueSpiral,
+    hue_spiral_swirl: HueSpiral,
+    fireworks: FireWorks,
+    fire_red: Fire,
+    fire_green: Fire,
+    eu_stars: Stars,
+    eo_stars: Stars,
+    falling_sparks: SparkFall,
+    snow_sparks: SnowSparks,
+    snake_show: SnakeShow,
+    particle_crash: ParticleCrash,
+    particle_crash_spiral: ParticleCrash,
+    sine_show: SineShow,
+    planet_show: PlanetShow,
+    sea_wave_chaos: SeaWave,
+    sea_wave_order: SeaWave,
+    //sea_wave_varying: SeaWave,
+    light_snow: Snow<90>,
+    heavy_snow: Snow<180>,
+}
+
+impl Programme {
+    pub fn new() -> Programme {
+        Programme {
+            hue_spiral_lift: HueSpiral::new_lift(),
+            hue_spiral_swirl: HueSpiral::new_swirl(),
+            fireworks: FireWorks::new(),
+            fire_red: Fire::new_red(),
+            fire_green: Fire::new_green(),
+            eu_stars: Stars::new("eu_stars", DARK_BLUE, YELLOW),
+            eo_stars: Stars::new("eo_stars", DARK_GREEN, WHITE),
+            falling_sparks: SparkFall::new(),
+            snow_sparks: SnowSparks::new(),
+            snake_show: SnakeShow::new(),
+            particle_crash: ParticleCrash::new(),
+            particle_crash_spiral: ParticleCrash::new_spiral(),
+            sine_show: SineShow::new(),
+            planet_show: PlanetShow::new(),
+            sea_wave_chaos: SeaWave::new("sea_wave_chaos", 0.63, 0.5, Some(9800), 8),
+            sea_wave_order: SeaWave::new("sea_wave_order", 0.99, 0.99, Some(1050), 12),
+            //sea_wave_varying: SeaWave::new("sea_wave_varying", None, 12),
+            light_snow: Snow::new("snow"),
+            heavy_snow: Snow::new("heavy_snow"),
+        }
+    }
+
+    pub fn shows<H: Hardware>(&mut self) -> [&mut dyn Show<H>; SHOW_NUM] {
+        [
+//            &mut self.sea_wave_varying,
+            &mut self.light_snow,
+            &mut self.sine_show,
+            &mut self.particle_crash_spiral,
+            &mut self.planet_show,
+            &mut self.fire_green,
+            &mut self.hue_spiral_lift,
+            &mut self.heavy_snow,
+            &mut self.sea_wave_chaos,
+            &mut self.fireworks,
+            &mut self.hue_spiral_swirl,
+            &mut self.snow_sparks,
+            &mut self.eo_stars,
+            &mut self.particle_crash,
+            &mut self.sea_wave_order,
+            &mut self.snake_show,
+            &mut self.falling_sparks,
+            &mut self.eu_stars,
+            &mut self.fire_red,
+        ]
+    }
+}
@@ -0,0 +1,228 @@
+//! The data sent down a chain of LEDs
+//!
+//! A `Frame` holds 4 bytes per LED.  For the APA102 and its SK9822 clone these
+//! are the header byte and the channels, framed by the start and the end
+//! frame.  For the single wire WS2812 and SK6812 they are the word the PIO
+//! program shifts out, the line going idle after the last LED latches them.
+
+use crate::apa102;
+use crate::ws2812;
+
+pub const LED_SIZE: usize = 4;
+/// 32 zero bits in front of the first APA102.
+pub const START_FRAME: usize = 4;
+/// 32 zero bits after the last LED, latching the data of a SK9822.
+const RESET_FRAME: usize = 4;
+
+/// The LED chips the strips are made of
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Protocol {
+    Apa102,
+    /// The APA102 clone, it shows new data only after a reset frame.
+    Sk9822,
+    Ws2812,
+    /// The WS2812 variant with an additional white LED.
+    Sk6812Rgbw
+}
+
+impl Protocol {
+    /// Whether the LEDs have data and clock lines, sent over SPI.
+    pub const fn is_clocked(self) -> bool {
+        matches!(self, Protocol::Apa102 | Protocol::Sk9822)
+    }
+}
+
+/// The order of the channels, as sent
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChannelOrder {
+    Rgb,
+    Rbg,
+    /// The order of the WS2812.
+    Grb,
+    Gbr,
+    Brg,
+    /// The order of genuine APA102s.
+    Bgr
+}
+
+impl ChannelOrder {
+    /// The offsets of the red, green and blue byte from the header byte of an
+    /// APA102, one more than their position in the word of a WS2812.
+    pub const fn offsets(self) -> [usize; 3] {
+        match self {
+            ChannelOrder::Rgb => [1, 2, 3],
+            ChannelOrder::Rbg => [1, 3, 2],
+            ChannelOrder::Grb => [2, 1, 3],
+            ChannelOrder::Gbr => [3, 1, 2],
+            ChannelOrder::Brg => [2, 3, 1],
+            ChannelOrder::Bgr => [3, 2, 1]
+        }
+    }
+}
+
+/// The bytes sent down a chain of LEDs: the start frame, 4 bytes per LED and
+/// the end frame
+///
+/// Every APA102 passes the clock on half a cycle late, so the data for the
+/// last LED is only through after another `leds / 2` clock edges.  These come
+/// from the zero bits of the end frame.  The SK9822 needs its reset frame
+/// before.  The single wire LEDs have neither.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Frame {
+    protocol: Protocol,
+    order: ChannelOrder,
+    leds: usize
+}
+
+impl Frame {
+    pub const fn new(protocol: Protocol, leds: usize) -> Frame {
+        Frame { protocol, order: ChannelOrder::Bgr, leds }
+    }
+
+    pub const fn with_order(mut self, order: ChannelOrder) -> Frame {
+        self.order = order;
+        self
+    }
+
+    /// The size of a buffer for `leds` LEDs split into up to `chains` frames.
+    pub const fn max_len(leds: usize, chains: usize) -> usize {
+        chains * (START_FRAME + RESET_FRAME + 1) + leds * LED_SIZE + leds / 16
+    }
+
+    pub const fn leds(&self) -> usize {
+        self.leds
+    }
+
+    pub const fn start_len(&self) -> usize {
+        if self.protocol.is_clocked() { START_FRAME } else { 0 }
+    }
+
+    pub const fn end_len(&self) -> usize {
+        let latch = self.leds.div_ceil(16);
+        match self.protocol {
+            Protocol::Apa102 => latch,
+            Protocol::Sk9822 => RESET_FRAME + latch,
+            Protocol::Ws2812 | Protocol::Sk6812Rgbw => 0
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.start_len() + self.leds * LED_SIZE + self.end_len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes the start and end frame to `bytes`, the frame starts at 0.
+    pub fn write_framing(&self, bytes: &mut [u8]) {
+        let end = self.start_len() + self.leds * LED_SIZE;
+        bytes[..self.start_len()].fill(0);
+        bytes[end..self.len()].fill(0);
+    }
+
+    /// Writes LED `led` with 16 bit intensities (red, green, blue) dimmed by
+    /// `master` to `bytes`.  `hdr` is passed on to `apa102::encode()`.
+    pub fn write_led(&self, bytes: &mut [u8], led: usize, rgb: [u16; 3], master: u8, hdr: bool) {
+        let offset = self.start_len() + led * LED_SIZE;
+        let led_bytes = &mut bytes[offset..offset + LED_SIZE];
+        match self.protocol {
+            Protocol::Apa102 | Protocol::Sk9822 => {
+                let (global, channels) = apa102::encode(rgb, master, hdr);
+                led_bytes[0] = apa102::header(global);
+                for (channel, channel_offset) in channels.into_iter().zip(self.order.offsets()) {
+                    led_bytes[channel_offset] = channel;
+                }
+            }
+            Protocol::Ws2812 | Protocol::Sk6812Rgbw => {
+                let rgbw = self.protocol == Protocol::Sk6812Rgbw;
+                let word = ws2812::encode(rgb, master, self.order, rgbw);
+                led_bytes.copy_from_slice(&word.to_be_bytes());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apa102::widen;
+    use proptest::prelude::*;
+
+    fn stream(frame: Frame, leds: &[[u8; 3]], master: u8, hdr: bool) -> Vec<u8> {
+        let mut bytes = vec![0x55; frame.len()];
+        frame.write_framing(&mut bytes);
+        for (i, led) in leds.iter().enumerate() {
+            frame.write_led(&mut bytes, i, led.map(widen), master, hdr);
+        }
+        bytes
+    }
+
+    #[test]
+    fn apa102_byte_stream() {
+        let frame = Frame::new(Protocol::Apa102, 2);
+        assert_eq!(
+            stream(frame, &[[1, 2, 3], [0, 0, 16]], 255, false),
+            [0, 0, 0, 0, 0xff, 3, 2, 1, 0xff, 16, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn sk9822_byte_stream() {
+        let frame = Frame::new(Protocol::Sk9822, 1);
+        assert_eq!(stream(frame, &[[255, 0, 0]], 128, false), [0, 0, 0, 0, 0xf0, 0, 0, 255, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn ws2812_byte_stream() {
+        let frame = Frame::new(Protocol::Ws2812, 2).with_order(ChannelOrder::Grb);
+        assert_eq!(stream(frame, &[[1, 2, 3], [255, 0, 0]], 255, false), [2, 1, 3, 0, 0, 255, 0, 0]);
+    }
+
+    #[test]
+    fn sk6812_byte_stream() {
+        let frame = Frame::new(Protocol::Sk6812Rgbw, 1).with_order(ChannelOrder::Grb);
+        assert_eq!(stream(frame, &[[200, 50, 80]], 255, false), [0, 150, 30, 50]);
+    }
+
+    #[test]
+    fn channel_orders() {
+        let led = |order| {
+            let frame = Frame::new(Protocol::Apa102, 1).with_order(order);
+            stream(frame, &[[1, 2, 3]], 255, false)[START_FRAME..START_FRAME + LED_SIZE].to_vec()
+        };
+        assert_eq!(led(ChannelOrder::Rgb), [0xff, 1, 2, 3]);
+        assert_eq!(led(ChannelOrder::Rbg), [0xff, 1, 3, 2]);
+        assert_eq!(led(ChannelOrder::Grb), [0xff, 2, 1, 3]);
+        assert_eq!(led(ChannelOrder::Gbr), [0xff, 2, 3, 1]);
+        assert_eq!(led(ChannelOrder::Brg), [0xff, 3, 1, 2]);
+        assert_eq!(led(ChannelOrder::Bgr), [0xff, 3, 2, 1]);
+    }
+
+    #[test]
+    fn end_frame_grows_with_the_chain() {
+        assert_eq!(Frame::new(Protocol::Apa102, 0).end_len(), 0);
+        assert_eq!(Frame::new(Protocol::Apa102, 16).end_len(), 1);
+        assert_eq!(Frame::new(Protocol::Apa102, 17).end_len(), 2);
+        assert_eq!(Frame::new(Protocol::Apa102, 720).end_len(), 45);
+        assert_eq!(Frame::new(Protocol::Sk9822, 720).end_len(), 49);
+        assert_eq!(Frame::new(Protocol::Apa102, 720).len(), 4 + 2880 + 45);
+        assert_eq!(Frame::new(Protocol::Ws2812, 720).len(), 2880);
+    }
+
+    proptest! {
+        #[test]
+        fn end_frame_has_a_clock_edge_per_two_leds(leds in 0usize..2000, sk9822: bool) {
+            let protocol = if sk9822 { Protocol::Sk9822 } else { Protocol::Apa102 };
+            let frame = Frame::new(protocol, leds);
+            prop_assert!(frame.end_len() * 8 >= leds / 2);
+        }
+
+        #[test]
+        fn two_chains_fit_the_buffer(leds in 0usize..2000, split in 0usize..2000) {
+            let split = split.min(leds);
+            let len = Frame::new(Protocol::Sk9822, split).len() + Frame::new(Protocol::Sk9822, leds - split).len();
+            prop_assert!(len <= Frame::max_len(leds, 2));
+        }
+    }
+}
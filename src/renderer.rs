@@ -0,0 +1,131 @@
+//! The shows on core 1
+//!
+//! `Renderer` is the `Hardware` the shows run against.  The LEDs, buttons and
+//! USB belong to core 0, they are reached through the handoff of every frame,
+//! see `lumio::link`.
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use embedded_hal::blocking::delay::DelayMs;
+use rp_pico::hal::{pac, sio::{Sio, SioFifo}, Timer};
+
+use lumio::button::ButtonState;
+use lumio::command::{LineReader, Overflow};
+use lumio::hardware::{Hardware, Instant};
+use lumio::ledstrip::LEDStrip;
+use lumio::link::{Handoff, Input};
+use lumio::random::Random;
+use lumio::stream::LiveFrame;
+use lumio::text::{Line, Text};
+
+/// The text for USB per frame, it has to take the answer to `list`.
+const LOG_LENGTH: usize = 512;
+
+pub struct Renderer {
+    led_strip: &'static mut LEDStrip,
+    random: Random,
+    timer: Timer,
+    fifo: SioFifo,
+    input: Input,
+    led: bool,
+    auto_show: Option<bool>,
+    log: Text<LOG_LENGTH>,
+    received: LineReader,
+    live: LiveFrame,
+    /// Whether `live` is not yet on the LED strip.
+    fresh: bool,
+}
+
+impl Renderer {
+    /// Has to be called on core 1, it takes the FIFO of the core it runs on.
+    pub fn new(led_strip: &'static mut LEDStrip, timer: Timer) -> Renderer {
+        // Each core sees its own end of the FIFO at the same address
+        let pac = unsafe { pac::Peripherals::steal() };
+        let fifo = Sio::new(pac.SIO).fifo;
+
+        Renderer {
+            led_strip,
+            random: Random::new(423434859),
+            timer,
+            fifo,
+            input: Input::new(),
+            led: false,
+            auto_show: None,
+            log: Text::new(),
+            received: LineReader::new(),
+            live: LiveFrame::new(),
+            fresh: false,
+        }
+    }
+}
+
+impl Hardware for Renderer {
+    fn get_time(&self) -> Instant {
+        self.timer.get_counter()
+    }
+
+    fn led_strip(&mut self) -> &mut LEDStrip { self.led_strip }
+    fn random(&mut self) -> &mut Random { &mut self.random }
+    fn do_next(&mut self) -> bool { self.input.take_next() }
+    fn auto_show(&mut self) -> bool { self.input.auto() }
+    fn set_auto_show(&mut self, on: bool) {
+        self.auto_show = Some(on);
+    }
+    fn button_state(&mut self) -> ButtonState { self.input.take_button() }
+    fn led_on(&mut self) {
+        self.led = true;
+    }
+    fn led_off(&mut self) {
+        self.led = false;
+    }
+    fn write_frame(&mut self) {
+        self.led_strip.process();
+
+        let mut handoff = Handoff {
+            led_strip: self.led_strip,
+            log: self.log.as_bytes(),
+            led: self.led,
+            auto_show: self.auto_show.take(),
+            received: &mut self.received,
+            live: &mut self.live,
+            fresh: &mut self.fresh,
+        };
+        // Core 0 uses the handoff until it answers
+        compiler_fence(Ordering::SeqCst);
+        self.fifo.write_blocking(&mut handoff as *mut Handoff as u32);
+        let input = Input::from_word(self.fifo.read_blocking());
+        compiler_fence(Ordering::SeqCst);
+
+        self.input.merge(input);
+        self.log.clear();
+    }
+
+    fn delay_ms(&mut self, delay: u32) {
+        self.timer.delay_ms(delay);
+    }
+
+    fn wait_until(&mut self, time: Instant) {
+        while self.timer.get_counter() < time {}
+    }
+
+    fn log(&mut self, message: &[u8]) {
+        self.log.push(message);
+    }
+
+    fn read_line(&mut self) -> Option<Result<Line, Overflow>> {
+        self.received.read_line()
+    }
+
+    fn has_live_frame(&self) -> bool {
+        self.fresh
+    }
+
+    fn write_live_frame(&mut self) -> bool {
+        if core::mem::take(&mut self.fresh) {
+            self.live.write_to(self.led_strip);
+            true
+        } else {
+            false
+        }
+    }
+}
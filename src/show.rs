@@ -0,0 +1,288 @@
+use core::fmt::Write;
+
+use crate::button::ButtonState;
+use crate::command::{Command, Overflow};
+use crate::conf::{FRAME_RATE, LIVE_TIMEOUT, TRANSITION};
+use crate::frameclock::{Delta, FrameClock};
+use crate::hardware::Hardware;
+use crate::led::Color;
+use crate::playlist::{Duration, Entry, Playlist};
+use crate::text::Line;
+
+/// A light show as seen by the show runner in `main()`.
+///
+/// The runner calls `init()` with the `Params` of the playlist entry when the
+/// show starts and then `frame()` once per frame, `FRAME_RATE` times per
+/// second.  `dt` is the time the frame covers, one frame unless rendering fell
+/// behind.  After each frame the LED strip is written out and presses of the
+/// second button are passed to `on_button()`.  When the first button asks for
+/// the next show or the show has run its time, `teardown()` is called and the
+/// LED strip is blacked out, its last frame kept for the transition into the
+/// next show.  The time only runs while the shows change on their own.
+pub trait Show<H: Hardware> {
+    fn name(&self) -> &'static str;
+
+    fn init(&mut self, _interface: &mut H, _params: Params) {}
+
+    fn frame(&mut self, interface: &mut H, dt: Delta);
+
+    fn on_button(&mut self, _state: ButtonState, _interface: &mut H) {}
+
+    /// Whether the show has come to an end, for entries that run until then.
+    fn is_finished(&self) -> bool {
+        false
+    }
+
+    fn teardown(&mut self, _interface: &mut H) {}
+}
+
+/// The settings of a show for a playlist entry.  A show takes those it has a
+/// use for, for those left out it keeps its own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Params {
+    colors: Option<[Color; 2]>
+}
+
+impl Params {
+    pub const fn new() -> Params {
+        Params { colors: None }
+    }
+
+    /// The colour of the background and of what moves in front of it, e.g.
+    /// the sky and the stars of `stars`.
+    pub const fn with_colors(mut self, background: Color, foreground: Color) -> Params {
+        self.colors = Some([background, foreground]);
+        self
+    }
+
+    pub const fn colors(&self) -> Option<[Color; 2]> {
+        self.colors
+    }
+}
+
+/// Why `run_show()` or `run_live()` ended.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ending {
+    /// On to the next entry of the playlist.
+    Next,
+    /// Back to the entry played before, asked for over USB.
+    Prev,
+    /// The show with that index, asked for over USB.
+    Select(usize),
+    /// The host started to stream frames.
+    Live
+}
+
+/// Runs the next entry of `playlist`, the show of that name out of `shows`,
+/// and what is selected or streamed over USB while it runs.
+pub fn run_next<H: Hardware>(playlist: &mut Playlist, shows: &mut [&mut dyn Show<H>], interface: &mut H) {
+    let mut entry = playlist.next(interface.random());
+    let mut from_playlist = true;
+    loop {
+        let Some(index) = shows.iter().position(|show| show.name() == entry.show()) else {
+            let mut line = Line::new();
+            let _ = writeln!(line, "no show named {}", entry.show());
+            interface.log(line.as_bytes());
+            return;
+        };
+        let mut ending = run_show(shows, index, interface, entry);
+        if ending == Ending::Live {
+            ending = run_live(shows, interface);
+            from_playlist = false;
+        }
+        match ending {
+            Ending::Next | Ending::Live => return,
+            Ending::Prev => {
+                // A selected show or the host goes back to the entry it
+                // interrupted
+                let previous = if from_playlist { playlist.back() } else { playlist.current() };
+                entry = previous.unwrap_or(entry);
+                from_playlist = true;
+            }
+            Ending::Select(index) => {
+                // As the playlist would play it, if it does
+                let show = shows[index].name();
+                entry = playlist.entry(show).unwrap_or(Entry::new(show));
+                from_playlist = false;
+            }
+        }
+    }
+}
+
+/// Runs show `index` out of `shows` as `entry` says, for its duration or until
+/// the first button or a command asks for another show.
+pub fn run_show<H: Hardware>(
+    shows: &mut [&mut dyn Show<H>],
+    index: usize,
+    interface: &mut H,
+    entry: Entry
+) -> Ending {
+    interface.log(shows[index].name().as_bytes());
+    interface.log(b"\n");
+
+    shows[index].init(interface, entry.params());
+    let mut clock = FrameClock::new(FRAME_RATE);
+    let mut start = interface.get_time();
+    let begin = start;
+    let ending = loop {
+        let due = clock.next_frame().max(interface.get_time());
+        interface.led_strip().set_mix(entry.transition().mix(due - begin));
+        if run_frame(shows[index], interface, &mut clock) {
+            break Ending::Next;
+        }
+        if let Some(ending) = after_frame(shows, shows[index].name(), interface) {
+            break ending;
+        }
+        if interface.has_live_frame() {
+            break Ending::Live;
+        }
+
+        let now = interface.get_time();
+        if !interface.auto_show() {
+            start = now;
+            continue;
+        }
+        let over = match entry.duration() {
+            Duration::For(duration) => now - start >= duration,
+            Duration::UntilFinished => shows[index].is_finished()
+        };
+        if over {
+            break Ending::Next;
+        }
+    };
+    interface.led_strip().keep_frame();
+    interface.led_strip().set_mix(None);
+    shows[index].teardown(interface);
+
+    interface.led_strip().black();
+    interface.led_off();
+    ending
+}
+
+/// Shows the frames the host streams until none came for `LIVE_TIMEOUT`, the
+/// first button asks for the next show or a command for another one.
+pub fn run_live<H: Hardware>(shows: &[&mut dyn Show<H>], interface: &mut H) -> Ending {
+    interface.log(b"live\n");
+
+    let mut clock = FrameClock::new(FRAME_RATE);
+    let begin = interface.get_time();
+    let mut last = begin;
+    let ending = loop {
+        interface.wait_until(clock.next_frame());
+        let now = interface.get_time();
+        clock.tick(now);
+        if interface.write_live_frame() {
+            last = now;
+        } else if now - last >= LIVE_TIMEOUT {
+            break Ending::Next;
+        }
+        interface.led_strip().set_mix(TRANSITION.mix(now - begin));
+        interface.write_frame();
+        if interface.do_next() {
+            break Ending::Next;
+        }
+        if let Some(ending) = after_frame(shows, "live", interface) {
+            break ending;
+        }
+    };
+    interface.led_strip().keep_frame();
+    interface.led_strip().set_mix(None);
+    interface.led_strip().black();
+    ending
+}
+
+/// Logs the power report of the frame written out last and carries out the
+/// commands received meanwhile, `current` is what runs.  Returns how that ends
+/// if a command says so.
+fn after_frame<H: Hardware>(shows: &[&mut dyn Show<H>], current: &str, interface: &mut H) -> Option<Ending> {
+    if let Some(report) = interface.led_strip().take_power_report() {
+        let mut line = Line::new();
+        let _ = writeln!(line, "{report}");
+        interface.log(line.as_bytes());
+    }
+
+    while let Some(line) = interface.read_line() {
+        if let Some(ending) = obey(line, shows, current, interface) {
+            return Some(ending);
+        }
+    }
+    None
+}
+
+/// Carries out the command in `line` while `current` runs and answers it,
+/// returns how that ends if it does.
+fn obey<H: Hardware>(
+    line: Result<Line, Overflow>,
+    shows: &[&mut dyn Show<H>],
+    current: &str,
+    interface: &mut H
+) -> Option<Ending> {
+    let mut reply = Line::new();
+    let line = match line {
+        Ok(line) => line,
+        Err(overflow) => {
+            let _ = writeln!(reply, "error: {overflow}");
+            interface.log(reply.as_bytes());
+            return None;
+        }
+    };
+    let ending = match Command::parse(line.as_bytes()) {
+        Ok(Command::Next) => Some(Ending::Next),
+        Ok(Command::Prev) => Some(Ending::Prev),
+        Ok(Command::Select(name)) => match shows.iter().position(|show| show.name() == name) {
+            Some(selected) => Some(Ending::Select(selected)),
+            None => {
+                let _ = writeln!(reply, "error: no show named {name}");
+                None
+            }
+        },
+        Ok(Command::Auto(on)) => {
+            interface.set_auto_show(on);
+            None
+        }
+        Ok(Command::Brightness(brightness)) => {
+            interface.led_strip().set_brightness(brightness);
+            None
+        }
+        Ok(Command::Status) => {
+            let auto = if interface.auto_show() { "on" } else { "off" };
+            let brightness = interface.led_strip().brightness();
+            let _ = writeln!(reply, "show {current} auto {auto} brightness {brightness}");
+            None
+        }
+        Ok(Command::List) => {
+            for show in shows.iter() {
+                interface.log(show.name().as_bytes());
+                interface.log(b"\n");
+            }
+            None
+        }
+        Ok(Command::Opc) => None,
+        Err(error) => {
+            let _ = writeln!(reply, "error: {error}");
+            None
+        }
+    };
+    if reply.is_empty() {
+        let _ = writeln!(reply, "ok");
+    }
+    interface.log(reply.as_bytes());
+    ending
+}
+
+/// Renders and writes out one frame of `show` when `clock` says it is due,
+/// returns whether the show is supposed to end.
+pub fn run_frame<H: Hardware>(show: &mut dyn Show<H>, interface: &mut H, clock: &mut FrameClock) -> bool {
+    interface.wait_until(clock.next_frame());
+    let dt = clock.tick(interface.get_time());
+    interface.led_strip().set_delta(dt);
+    show.frame(interface, dt);
+    interface.write_frame();
+
+    let state = interface.button_state();
+    if state == ButtonState::ShortPressed || state == ButtonState::LongPressed {
+        show.on_button(state, interface);
+    }
+
+    interface.do_next()
+}
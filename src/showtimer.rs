@@ -1,49 +1,42 @@
-use embedded_hal::digital::v2::OutputPin;
-use rp_pico::hal::gpio::{PinId, Pin, PullDown, FunctionSio, SioOutput};
-use rp_pico::hal::timer::Instant;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
 
 use crate::button::{Button, ButtonState};
-use crate::conf::AUTO_SHOW_DELAY;
+use crate::hardware::Instant;
 
-pub struct ShowTimer<BP: PinId, LP: PinId> {
+/// The first button: a short press asks for the next show, a long press turns
+/// the automatic change of the shows on and off, shown by its LED.  How long
+/// a show runs is up to the show runner.
+pub struct ShowTimer<BP: InputPin, LP: OutputPin> {
     auto_show: bool,
     button: Button<BP>,
-    led_pin: Pin<LP, FunctionSio<SioOutput>, PullDown>,
-    time_stamp: Instant,
+    led_pin: LP,
 }
 
-impl <BP: PinId, LP: PinId> ShowTimer<BP, LP> {
-    pub fn new(
-        button: Button<BP>,
-        led_pin: Pin<LP, FunctionSio<SioOutput>, PullDown>,
-        time_stamp: Instant
-    ) -> ShowTimer<BP, LP> {
-        ShowTimer { auto_show: true, button, led_pin, time_stamp }
+impl <BP: InputPin, LP: OutputPin> ShowTimer<BP, LP> {
+    pub fn new(button: Button<BP>, led_pin: LP) -> ShowTimer<BP, LP> {
+        ShowTimer { auto_show: true, button, led_pin }
     }
 
     pub fn do_next(&mut self, current_time: Instant) -> bool {
         let mut do_next = false;
         match self.button.state(current_time) {
-            ButtonState::ShortPressed => {
-                do_next = true;
-                self.time_stamp = current_time;
-            },
-            ButtonState::LongPressed => {
-                self.auto_show = !self.auto_show;
-                self.time_stamp = current_time;
-            },
+            ButtonState::ShortPressed => do_next = true,
+            ButtonState::LongPressed => self.auto_show = !self.auto_show,
             _ => {}
         }
         if self.auto_show {
             let _ = self.led_pin.set_high();
-            let now = current_time;
-            if now - self.time_stamp > AUTO_SHOW_DELAY {
-                self.time_stamp = now;
-                do_next = true;
-            }
         } else {
             let _ = self.led_pin.set_low();
         }
         do_next
     }
+
+    pub fn auto_show(&self) -> bool {
+        self.auto_show
+    }
+
+    pub fn set_auto_show(&mut self, on: bool) {
+        self.auto_show = on;
+    }
 }
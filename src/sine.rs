@@ -1,7 +1,9 @@
 use crate::{
     conf::*,
-    interface::Interface,
+    frameclock::Delta,
+    hardware::Hardware,
     led::{Color, WHITE},
+    show::{Params, Show},
 };
 
 
@@ -31,13 +33,14 @@ impl Sine {
         self
     }
 
-    fn process(&mut self) -> isize {
+    /// Returns the position and swings on by `dt`.
+    fn process(&mut self, dt: Delta) -> isize {
         let pos: isize = self.current >> 6;
 
         let accel = scale(self.elastic, pos - self.center) << 2;
-        self.speed = ((self.speed << 2 ) - accel) >> 2;
+        self.speed = ((self.speed << 2 ) - dt.scale(accel)) >> 2;
 
-        self.current = self.current + self.speed;
+        self.current += dt.scale(self.speed);
 
         pos
     }
@@ -45,80 +48,85 @@ impl Sine {
 
 
 pub struct SineShow {
-    sine: Sine
+    sine: Sine,
+    hue: f32
 }
 
 
 impl SineShow {
     pub fn new() -> SineShow {
-        SineShow { sine: Sine::new(30, 502, 28) }
+        SineShow { sine: Sine::new(30, 502, 28), hue: 0.0 }
     }
 
-    pub fn show(&mut self, interface: &mut Interface) {
-        let mut hue = 0.0f32;
-        let hue_step = STRIP_NUM as f32 / 360.0;
-        loop {
-            interface.led_strip().black();
+    fn sort(&self, p1: isize, p2: isize, p3: isize) -> (isize, isize, isize) {
+        if p1 < p2 {
+            if p2 < p3 {
+                (p1, p2, p3)
+            } else if p1 < p3 {
+                (p1, p3, p2)
+            } else {
+                (p3, p1, p2)
+            }
+        } else if p1 < p3 {
+            (p2, p1, p3)
+        } else if p2 < p3 {
+            (p2, p3, p1)
+        } else {
+            (p3, p2, p1)
+        }
+    }
+}
 
-            let wave_pos: [isize; 3*STRIP_NUM] = core::array::from_fn(|_i| self.sine.process());
+impl<H: Hardware> Show<H> for SineShow {
+    fn name(&self) -> &'static str {
+        "sine"
+    }
 
-            for i in 0..STRIP_NUM {
-                let strip_begin = (i % STRIP_NUM * STRIP_LENGTH) as isize;
+    fn init(&mut self, _interface: &mut H, _params: Params) {
+        self.hue = 0.0;
+    }
 
-                let pos_1 = wave_pos[i];
-                let pos_2 = wave_pos[i+STRIP_NUM];
-                let pos_3 = wave_pos[i+2*STRIP_NUM];
+    fn frame(&mut self, interface: &mut H, dt: Delta) {
+        let layout = interface.led_strip().layout();
+        let strips = layout.strips();
+        let hue_step = strips as f32 / 360.0;
 
-                interface.led_strip().set_led(strip_begin + pos_1, WHITE);
-                interface.led_strip().set_led(strip_begin + pos_2, WHITE);
-                interface.led_strip().set_led(strip_begin + pos_3, WHITE);
+        interface.led_strip().black();
 
-                let (pos_1, pos_2, pos_3) = self.sort(pos_1, pos_2, pos_3);
+        let mut wave_pos = [0isize; 3*MAX_STRIPS];
+        for pos in wave_pos[..3*strips].iter_mut() {
+            *pos = self.sine.process(dt);
+        }
 
-                hue += 15.0 / 360.0;
+        for i in 0..strips {
+            let strip_begin = layout.index(i, 0) as isize;
 
-                let color = Color::from_hsv(hue, 1.0, 0.25);
+            let pos_1 = wave_pos[i];
+            let pos_2 = wave_pos[i+strips];
+            let pos_3 = wave_pos[i+2*strips];
 
-                for p in pos_1+1..pos_2 {
-                    interface.led_strip().set_led(strip_begin + p, color);
-                }
+            interface.led_strip().set_led(strip_begin + pos_1, WHITE);
+            interface.led_strip().set_led(strip_begin + pos_2, WHITE);
+            interface.led_strip().set_led(strip_begin + pos_3, WHITE);
 
-                let color = Color::from_hsv(hue+0.5, 1.0, 0.25);
+            let (pos_1, pos_2, pos_3) = self.sort(pos_1, pos_2, pos_3);
 
-                for p in pos_2+1..pos_3 {
-                    interface.led_strip().set_led(strip_begin + p, color);
-                }
-            }
+            self.hue += 15.0 / 360.0;
 
-            hue += hue_step / 20.0;
+            let color = Color::from_hsv(self.hue, 1.0, 0.25);
 
-            interface.write_spi();
-            if interface.do_next() {
-                interface.led_strip().black();
-                let _ = interface.led_off();
-                break;
+            for p in pos_1+1..pos_2 {
+                interface.led_strip().set_led(strip_begin + p, color);
             }
-        }
-    }
 
-    fn sort(&self, p1: isize, p2: isize, p3: isize) -> (isize, isize, isize) {
-        if p1 < p2 {
-            if p2 < p3 {
-                return (p1, p2, p3)
-            } else if p1 < p3 {
-                return (p1, p3, p2)
-            } else {
-                return (p3, p1, p2)
-            }
-        } else {
-            if p1 < p3 {
-                return (p2, p1, p3)
-            } else if p2 < p3 {
-                return (p2, p3, p1)
-            } else {
-                return (p3, p2, p1)
+            let color = Color::from_hsv(self.hue+0.5, 1.0, 0.25);
+
+            for p in pos_2+1..pos_3 {
+                interface.led_strip().set_led(strip_begin + p, color);
             }
         }
+
+        self.hue += hue_step / 20.0;
     }
 }
 
@@ -128,14 +136,17 @@ enum Elastic {
 }
 
 pub struct SeaWave {
+    name: &'static str,
     hue: f32,
     hue_spark: f32,
     elastic: Elastic,
-    ampl: isize
+    ampl: isize,
+    sine: Sine
 }
 
 impl SeaWave {
     pub fn new(
+        name: &'static str,
         hue: f32,
         hue_spark: f32,
         elastic: Option<isize>, ampl: isize
@@ -144,49 +155,80 @@ impl SeaWave {
             Some(v) => Elastic::Constant(v),
             None => Elastic::Varying(Sine::new(1100, 12, 200))
         };
-        SeaWave { hue, hue_spark, elastic, ampl }
+        SeaWave { name, hue, hue_spark, elastic, ampl, sine: Sine::new(40, 0, ampl) }
     }
 
-    fn elastic(&mut self) -> isize {
+    fn elastic(&mut self, dt: Delta) -> isize {
         match &mut self.elastic {
             Elastic::Constant(v) => *v,
-            Elastic::Varying(sine) => sine.process()
+            Elastic::Varying(sine) => sine.process(dt)
         }
     }
+}
 
-    pub fn show(&mut self, interface: &mut Interface) {
-        let mut sine = Sine::new(40, 0 , self.ampl);
-        loop {
-            interface.led_strip().black();
+impl<H: Hardware> Show<H> for SeaWave {
+    fn name(&self) -> &'static str {
+        self.name
+    }
 
-            sine.set_elastic(self.elastic());
+    fn init(&mut self, _interface: &mut H, _params: Params) {
+        self.sine = Sine::new(40, 0, self.ampl);
+    }
 
-            for i in 0..STRIP_NUM {
-                let strip_begin = (i % STRIP_NUM * STRIP_LENGTH) as isize;
+    fn frame(&mut self, interface: &mut H, dt: Delta) {
+        interface.led_strip().black();
 
-                let pos = sine.process();
-                for p in 0..pos {
-                    let hue = if interface.random().value8() < 32 {
-                        random_hue_around_given(self.hue_spark, interface)
-                    } else {
-                        self.hue
-                    };
-                    interface.led_strip().set_led(strip_begin + p, Color::from_hsv(hue, 1.0, 0.25));
-                }
+        let elastic = self.elastic(dt);
+        self.sine.set_elastic(elastic);
 
-            }
+        let layout = interface.led_strip().layout();
+        for i in 0..layout.strips() {
+            let strip_begin = layout.index(i, 0) as isize;
 
-            interface.write_spi();
-            if interface.do_next() {
-                interface.led_strip().black();
-                let _ = interface.led_off();
-                break;
+            let pos = self.sine.process(dt);
+            for p in 0..pos {
+                let hue = if interface.random().value8() < 32 {
+                    random_hue_around_given(self.hue_spark, interface)
+                } else {
+                    self.hue
+                };
+                interface.led_strip().set_led(strip_begin + p, Color::from_hsv(hue, 1.0, 0.25));
             }
         }
     }
-
 }
 
-fn random_hue_around_given(center_hue: f32, interface: &mut Interface) -> f32 {
+fn random_hue_around_given<H: Hardware>(center_hue: f32, interface: &mut H) -> f32 {
     (center_hue - interface.random().value() / 6.0) % 1.0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sine_at_rest_stays_in_the_center() {
+        let mut sine = Sine::new(30, 502, 0);
+        for _ in 0..1000 {
+            assert_eq!(sine.process(Delta::ONE), 30);
+        }
+    }
+
+    #[test]
+    fn sine_swings_around_the_center_within_its_amplitude() {
+        let mut sine = Sine::new(30, 502, 28);
+        let positions: [isize; 1000] = core::array::from_fn(|_| sine.process(Delta::ONE));
+
+        assert!(positions.iter().all(|&pos| (0..=60).contains(&pos)));
+        assert!(positions.iter().any(|&pos| pos < 10));
+        assert!(positions.iter().any(|&pos| pos > 50));
+    }
+
+    #[test]
+    fn sine_without_elasticity_stands_still() {
+        let mut sine = Sine::new(40, 0, 10);
+        for _ in 0..100 {
+            assert_eq!(sine.process(Delta::ONE), 50);
+        }
+    }
+}
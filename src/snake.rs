@@ -2,7 +2,9 @@ use crate::conf::*;
 use crate::ledstrip::LEDStrip;
 use crate::led::{Color, BLACK};
 use crate::button::ButtonState;
-use crate::Interface;
+use crate::frameclock::Delta;
+use crate::hardware::Hardware;
+use crate::show::{Params, Show};
 
 #[derive(Clone, Copy)]
 pub struct Snake {
@@ -43,18 +45,19 @@ impl Snake {
         if self.done {
             return
         }
-        let pos = (STRIP_LENGTH*self.start_strip + self.step) as isize;
-        if self.step < STRIP_LENGTH {
+        let length = led_strip.layout().length();
+        let pos = led_strip.layout().index(self.start_strip, self.step) as isize;
+        if self.step < length {
             led_strip.set_led(pos, self.head_color);
         }
-        if self.step > 0 && self.step < STRIP_LENGTH + 1 {
+        if self.step > 0 && self.step < length + 1 {
             led_strip.set_led(pos-1, self.tail_color);
         }
-        if self.step > 1 && self.step < STRIP_LENGTH + 2{
+        if self.step > 1 && self.step < length + 2{
             led_strip.set_led_target(pos-2, BLACK, self.decay);
         }
 
-        if self.step == STRIP_LENGTH + 1 {
+        if self.step == length + 1 {
             self.done = true;
         }
         self.step += 1;
@@ -62,58 +65,74 @@ impl Snake {
 }
 
 pub struct SnakeShow {
-    constant_snakes: [Snake; STRIP_NUM],
-    random_snakes: [Snake; STRIP_NUM]
+    constant_snakes: [Snake; MAX_STRIPS],
+    random_snakes: [Snake; MAX_STRIPS],
+    running: bool,
+    step: usize
 }
 
 impl SnakeShow {
     pub fn new() -> SnakeShow {
         SnakeShow {
-            constant_snakes: [Snake::default(); STRIP_NUM],
-            random_snakes: [Snake::default(); STRIP_NUM]
+            constant_snakes: [Snake::default(); MAX_STRIPS],
+            random_snakes: [Snake::default(); MAX_STRIPS],
+            running: false,
+            step: 0
         }
     }
+}
 
-    pub fn show(&mut self, interface: &mut Interface) {
-        let mut running = false;
-        let mut step = 0;
+impl<H: Hardware> Show<H> for SnakeShow {
+    fn name(&self) -> &'static str {
+        "snake"
+    }
 
-        loop {
-            if !running {
-                for i in 0..STRIP_NUM {
-                    self.constant_snakes[i].reset(i, interface.random().value(), 60./360.);
-                }
-            }
-            if self.constant_snakes.iter().all(|sn| sn.is_done()) {
-                let _ = interface.led_off();
-                running = false;
-            }
-            if (interface.button_state() == ButtonState::ShortPressed && !running) || step == 0 {
-                let _ = interface.led_on();
-                running = true;
-            }
-            for sn in self.constant_snakes.iter_mut() {
-                sn.process(&mut interface.led_strip());
-            }
-            interface.write_spi();
-            if interface.random().value8() < SNAKE_PROB {
-                let cand = interface.random().value32(STRIP_NUM as u32) as usize;
-                if self.random_snakes[cand].is_done() {
-                    self.random_snakes[cand].reset(cand, interface.random().value(), 60./360.);
-                }
-            }
-            for sn in self.random_snakes.iter_mut() {
-                sn.process(&mut interface.led_strip());
+    fn init(&mut self, _interface: &mut H, _params: Params) {
+        self.running = false;
+        self.step = 0;
+    }
+
+    fn frame(&mut self, interface: &mut H, _dt: Delta) {
+        let strips = interface.led_strip().layout().strips();
+        if !self.running {
+            for i in 0..strips {
+                self.constant_snakes[i].reset(i, interface.random().value(), 60./360.);
             }
+        }
+        if self.constant_snakes.iter().all(|sn| sn.is_done()) {
+            interface.led_off();
+            self.running = false;
+        }
+        if self.step == 0 {
+            interface.led_on();
+            self.running = true;
+        }
+        for sn in self.constant_snakes.iter_mut() {
+            sn.process(interface.led_strip());
+        }
 
-            if interface.do_next() {
-                interface.led_strip().black();
-                let _ = interface.led_off();
-                break;
+        if interface.random().value8() < SNAKE_PROB {
+            let cand = interface.random().value32(strips as u32) as usize;
+            if self.random_snakes[cand].is_done() {
+                self.random_snakes[cand].reset(cand, interface.random().value(), 60./360.);
             }
+        }
+        for sn in self.random_snakes.iter_mut() {
+            sn.process(interface.led_strip());
+        }
+
+        self.step = (self.step + 1) % 1024;
+    }
+
+    /// A run of the snakes up all strips is over.
+    fn is_finished(&self) -> bool {
+        self.step > 0 && !self.running
+    }
 
-            step = (step + 1) % 1024;
-            //        interface.delay_ms(1);
+    fn on_button(&mut self, state: ButtonState, interface: &mut H) {
+        if state == ButtonState::ShortPressed && !self.running {
+            interface.led_on();
+            self.running = true;
         }
     }
 }
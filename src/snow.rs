@@ -1,4 +1,4 @@
-use crate::{button::ButtonState, conf::{STRIP_LENGTH, STRIP_NUM}, interface::Interface, led::{DARK_WHITE, GREEN, RED}};
+use crate::{button::ButtonState, conf::MAX_STRIPS, frameclock::Delta, hardware::Hardware, layout::Layout, led::{DARK_WHITE, GREEN, RED}, show::{Params, Show}};
 
 const SNOW_START_PROB: u32 = 4;
 const CHANGE_CONDITION: u32 = 5;
@@ -18,9 +18,10 @@ impl SnowFlake {
         }
     }
 
-    fn reset(&mut self, interface: &mut Interface) -> &mut SnowFlake {
-        self.strip = interface.random().value32(STRIP_NUM as u32) as isize;
-        self.alt = (STRIP_LENGTH << 6) as isize;
+    fn reset<H: Hardware>(&mut self, interface: &mut H) -> &mut SnowFlake {
+        let layout = interface.led_strip().layout();
+        self.strip = interface.random().value32(layout.strips() as u32) as isize;
+        self.alt = (layout.length() << 6) as isize;
         self
     }
 
@@ -28,107 +29,141 @@ impl SnowFlake {
         self.alt > 0
     }
 
-    fn process(&mut self, interface: &mut Interface, wind: i32) {
+    fn deactivate(&mut self) {
+        self.alt = 0;
+    }
+
+    fn process<H: Hardware>(&mut self, interface: &mut H, wind: i32) {
         if !self.is_active() {
             return
         }
 
+        let layout = interface.led_strip().layout();
+        let strips = layout.strips() as isize;
+
         let random = interface.random().value32(100);
         if random < CHANGE_CONDITION {
-            self.strip = if random % 2 == 0 {
+            self.strip = if random.is_multiple_of(2) {
                 self.strip + 1
             } else {
                 self.strip - 1
-            } % STRIP_NUM as isize;
+            } % strips;
         }
 
-        if interface.random().value8() % 2 == 0 {
+        if interface.random().value8().is_multiple_of(2) {
             self.strip += (wind / 32) as isize;
         }
-        self.strip %= STRIP_NUM as isize;
+        self.strip %= strips;
+        if !layout.wraps() {
+            self.strip = self.strip.clamp(0, strips - 1);
+        }
 
         self.alt -= FALL_SPEED;
     }
 
-    fn pos(&self) -> isize {
+    fn pos(&self, layout: Layout) -> isize {
         let led_alt = self.alt >> 6;
-        self.strip * STRIP_LENGTH as isize + led_alt
+        layout.pos(self.strip, led_alt)
     }
 
-    fn strip(&self) -> usize {
-        (self.strip as usize) % STRIP_NUM
+    fn strip(&self, layout: Layout) -> usize {
+        (self.strip as usize) % layout.strips()
     }
 }
 
 
-pub fn snow<const NUM_SNOW_FLAKES: usize>(interface: &mut Interface) {
-    let mut flakes: [SnowFlake; NUM_SNOW_FLAKES] = core::array::from_fn(|i| i+1).map(|_i| SnowFlake::new());
+pub struct Snow<const NUM_SNOW_FLAKES: usize> {
+    name: &'static str,
+    flakes: [SnowFlake; NUM_SNOW_FLAKES],
+    coverage: [usize; MAX_STRIPS],
+    wind: i32,
+    with_wind: bool,
+    wind_count: usize
+}
+
+impl<const NUM_SNOW_FLAKES: usize> Snow<NUM_SNOW_FLAKES> {
+    pub fn new(name: &'static str) -> Snow<NUM_SNOW_FLAKES> {
+        Snow {
+            name,
+            flakes: core::array::from_fn(|_i| SnowFlake::new()),
+            coverage: [0; MAX_STRIPS],
+            wind: 0,
+            with_wind: true,
+            wind_count: 1
+        }
+    }
+}
 
-    let mut coverage: [usize; STRIP_NUM] = [0; STRIP_NUM];
+impl<H: Hardware, const NUM_SNOW_FLAKES: usize> Show<H> for Snow<NUM_SNOW_FLAKES> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
 
-    let mut wind = 0i32;
-    let mut with_wind = true;
-    let mut wind_count = 1;
-    let _ = interface.led_on();
+    fn init(&mut self, interface: &mut H, _params: Params) {
+        for flake in self.flakes.iter_mut() {
+            flake.deactivate();
+        }
+        self.coverage = [0; MAX_STRIPS];
+        self.wind = 0;
+        self.with_wind = true;
+        self.wind_count = 1;
+        interface.led_on();
+    }
 
-    loop {
+    fn frame(&mut self, interface: &mut H, _dt: Delta) {
         interface.led_strip().black();
 
-        if with_wind {
-            wind_count = (wind_count + 1) % 3;
-            if wind_count == 0 {
-                wind = make_wind(interface, wind);
+        if self.with_wind {
+            self.wind_count = (self.wind_count + 1) % 3;
+            if self.wind_count == 0 {
+                self.wind = make_wind(interface, self.wind);
             }
         } else {
-            wind = 0;
+            self.wind = 0;
         }
 
-        for flake in flakes.iter_mut() {
+        let layout = interface.led_strip().layout();
+        for flake in self.flakes.iter_mut() {
             if !flake.is_active() && interface.random().value32(2048) < SNOW_START_PROB {
                 flake.reset(interface);
-                if coverage[flake.strip()] > 0 {
-                    coverage[flake.strip()] -= 1;
+                let strip = flake.strip(layout);
+                if self.coverage[strip] > 0 {
+                    self.coverage[strip] -= 1;
                 }
             }
             if flake.is_active() {
-                process_flake(interface, &mut coverage, flake,  wind);
+                process_flake(interface, &mut self.coverage, flake, self.wind);
             }
 
-            handle_coverage(interface, &mut coverage);
+            handle_coverage(interface, &mut self.coverage);
         }
 
-        //show_wind(interface, wind);
-
-        interface.write_spi();
+        //show_wind(interface, self.wind);
+    }
 
-        if interface.button_state() == ButtonState::LongPressed {
-            with_wind = !with_wind;
-            if with_wind {
-                let _ = interface.led_on();
+    fn on_button(&mut self, state: ButtonState, interface: &mut H) {
+        if state == ButtonState::LongPressed {
+            self.with_wind = !self.with_wind;
+            if self.with_wind {
+                interface.led_on();
             } else {
-                let _ = interface.led_off();
+                interface.led_off();
             }
         }
-
-
-        if interface.do_next() {
-            interface.led_strip().black();
-            let _ = interface.led_off();
-            break;
-        }
     }
 }
 
-fn make_wind(interface: &mut Interface, wind: i32) -> i32 {
+fn make_wind<H: Hardware>(interface: &mut H, wind: i32) -> i32 {
     if interface.random().value8() > 64 {
         return wind
     }
 
     let wind_change = interface.random().value32(5) as i32 - 2;
-    (wind + wind_change).min(32).max(-32)
+    (wind + wind_change).clamp(-32, 32)
 }
 
-fn show_wind(interface: &mut Interface, wind: i32) {
+#[allow(dead_code)]
+fn show_wind<H: Hardware>(interface: &mut H, wind: i32) {
     if wind < 0 {
         for i in 0..(-wind as usize) {
             interface.led_strip().set_led(i as isize, RED);
@@ -141,56 +176,56 @@ fn show_wind(interface: &mut Interface, wind: i32) {
     }
 }
 
-fn handle_coverage(interface: &mut Interface, coverage: &mut [usize; STRIP_NUM]) {
-    for strip in 0..STRIP_NUM {
-        average_coverage(strip, coverage);
+fn handle_coverage<H: Hardware>(interface: &mut H, coverage: &mut [usize; MAX_STRIPS]) {
+    let layout = interface.led_strip().layout();
+    for strip in 0..layout.strips() {
+        average_coverage(layout, strip, coverage);
         let mut local_coverage = coverage[strip];
 
         if local_coverage > 3 {
             local_coverage = 3 + (local_coverage-3) / 5;
         }
         for y in 0..local_coverage {
-            let pos = (strip * STRIP_LENGTH + y) as isize;
+            let pos = layout.index(strip, y) as isize;
             interface.led_strip().set_led(pos, DARK_WHITE);
         }
     }
 }
 
-fn process_flake(
-    interface: &mut Interface,
-    coverage: &mut [usize; STRIP_NUM],
+fn process_flake<H: Hardware>(
+    interface: &mut H,
+    coverage: &mut [usize; MAX_STRIPS],
     flake: &mut SnowFlake,
     wind: i32,
 ) {
+    let layout = interface.led_strip().layout();
     flake.process(interface, wind);
     if !flake.is_active() {
-        coverage[flake.strip()] += 1;
+        coverage[flake.strip(layout)] += 1;
     }
-    interface.led_strip().set_led(flake.pos(), DARK_WHITE);
+    interface.led_strip().set_led(flake.pos(layout), DARK_WHITE);
 }
 
-fn average_coverage(strip: usize, coverage: &mut [usize; STRIP_NUM]) {
-    let left_neighbor = if strip == 0 {
-        STRIP_NUM - 1
-    } else {
-        strip - 1
-    };
-    let right_neighbor = (strip + 1) % STRIP_NUM;
-    let cov_left = coverage[left_neighbor];
-    let cov_right = coverage[right_neighbor];
+fn average_coverage(layout: Layout, strip: usize, coverage: &mut [usize; MAX_STRIPS]) {
+    let left_neighbor = layout.neighbour(strip, -1);
+    let right_neighbor = layout.neighbour(strip, 1);
 
     let mut averaged_coverage = coverage[strip];
 
-    let diff_left = averaged_coverage as isize - cov_left as isize;
-    let diff_right = averaged_coverage as isize - cov_right as isize;
+    let diff = |neighbor: Option<usize>| match neighbor {
+        Some(n) => averaged_coverage as isize - coverage[n] as isize,
+        None => 0
+    };
+    let diff_left = diff(left_neighbor);
+    let diff_right = diff(right_neighbor);
 
-    if diff_left > 3 {
-        coverage[left_neighbor] += 1;
+    if let Some(n) = left_neighbor.filter(|_| diff_left > 3) {
+        coverage[n] += 1;
         averaged_coverage -= 1;
     }
 
-    if diff_right > 3 {
-        coverage[right_neighbor] += 1;
+    if let Some(n) = right_neighbor.filter(|_| diff_right > 3) {
+        coverage[n] += 1;
         averaged_coverage -= 1;
     }
 
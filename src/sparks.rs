@@ -1,9 +1,9 @@
 
-use crate::{ledstrip::LEDStrip, conf::*, led::{Color, self}, interface::Interface};
+use crate::{ledstrip::LEDStrip, conf::*, led::{Color, self}, hardware::Hardware, show::{Params, Show}};
+use crate::frameclock::Delta;
 use crate::button::ButtonState;
 
-const ISTRIP_LENGTH: isize = STRIP_LENGTH as isize;
-pub const SPARK_NUM: usize = STRIP_NUM * SPARKS_PER_STRIP;
+pub const SPARK_NUM: usize = MAX_STRIPS * SPARKS_PER_STRIP;
 
 const ACCEL: isize = 24;
 
@@ -54,14 +54,14 @@ impl MonoSpark {
         self.engine.reset(initial_speed, position);
     }
 
-    pub fn process(&mut self, led_strip: &mut LEDStrip) {
+    pub fn process(&mut self, led_strip: &mut LEDStrip, dt: Delta) {
         if !self.is_active() {
             return
         }
         let color = Color::from_hsv(self.hue, 1.0, self.current_brightness as f32 / 255.0);
-        self.engine.process(led_strip, color);
+        self.engine.process(led_strip, color, dt);
         if self.engine.going_down() || self.engine.accel == 0 {
-            self.current_brightness = led::decay(self.current_brightness, 0, self.decay);
+            self.current_brightness = led::decay(self.current_brightness, 0, dt.decay(self.decay));
         }
     }
 }
@@ -96,11 +96,11 @@ impl ColorSpark {
         self.engine.deactivate();
     }
 
-    pub fn process(&mut self, led_strip: &mut LEDStrip) {
+    pub fn process(&mut self, led_strip: &mut LEDStrip, dt: Delta) {
         let color = Color::from_hsv(self.hue, self.current_sat, 1.0);
-        self.engine.process(led_strip, color);
+        self.engine.process(led_strip, color, dt);
         if self.engine.going_down() {
-            self.current_sat += (1.0 - self.current_sat) * self.decay;
+            self.current_sat += (1.0 - self.current_sat) * dt.rate(self.decay);
         }
     }
 }
@@ -136,6 +136,10 @@ impl FallingSparks {
         self.engine.deactivate();
     }
 
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
     pub fn set_hue(&mut self, hue: f32) -> &mut FallingSparks {
         self.hue = Some(hue);
         self.sat = 1.0;
@@ -152,12 +156,12 @@ impl FallingSparks {
         self
     }
 
-    pub fn process(&mut self, led_strip: &mut LEDStrip) {
+    pub fn process(&mut self, led_strip: &mut LEDStrip, dt: Delta) {
         let color =match self.hue {
             Some(hue) => Color::from_hsv(hue, self.sat, 0.5),
             None => self.color
         };
-        self.engine.process(led_strip, color);
+        self.engine.process(led_strip, color, dt);
     }
 }
 
@@ -205,28 +209,29 @@ impl SparkEngine {
         self.current_speed < 0
     }
 
-    fn process(&mut self, led_strip: &mut LEDStrip, color: Color) {
+    /// Draws the spark and moves it on by `dt`.
+    fn process(&mut self, led_strip: &mut LEDStrip, color: Color, dt: Delta) {
         if !self.is_active() {
             return;
         }
-        let start_pos = ISTRIP_LENGTH*self.strip;
+        let layout = led_strip.layout();
 
-        let next_position = self.current_position + self.current_speed;
+        let next_position = self.current_position + dt.scale(self.current_speed);
 
         let pos = self.current_position >> 6;
-        if pos < ISTRIP_LENGTH {
-            led_strip.set_led(start_pos + pos, color);
+        if pos < layout.length() as isize {
+            led_strip.set_led(layout.pos(self.strip, pos), color);
         }
 
         self.current_position = next_position;
-        self.current_speed = ((self.current_speed << 2 ) - self.accel) >> 2 ;
+        self.current_speed = ((self.current_speed << 2 ) - dt.scale(self.accel)) >> 2 ;
     }
 }
 
 
 pub struct FireWorks {
     mono_sparks: [MonoSpark; SPARK_NUM],
-    color_sparks: [ColorSpark; STRIP_NUM]
+    color_sparks: [ColorSpark; MAX_STRIPS]
 
 }
 
@@ -234,47 +239,63 @@ impl FireWorks {
     pub fn new() -> FireWorks {
         FireWorks {
             mono_sparks: core::array::from_fn(|i| i+1).map(|sn| MonoSpark::new(sn / SPARKS_PER_STRIP)),
-            color_sparks: core::array::from_fn(|i| i+1).map(|strip| ColorSpark::new(strip))
+            color_sparks: core::array::from_fn(|i| i+1).map(ColorSpark::new)
         }
     }
 
-    pub fn show(&mut self, interface: &mut Interface) {
-        loop {
-            interface.led_strip().black();
-            for sp in self.mono_sparks.iter_mut() {
-                sp.process(&mut interface.led_strip());
-            }
-            for sp in self.color_sparks.iter_mut() {
-                sp.process(&mut interface.led_strip());
-            }
-            interface.write_spi();
-            if !self.mono_sparks.iter().any(|sp| sp.is_active()) {
-                if interface.random().value() < SPARK_PROB || interface.button_state() == ButtonState::ShortPressed {
-                    let _ = interface.led_on();
-                    let hue = interface.random().value();
-                    for sp in self.mono_sparks.iter_mut() {
-                        let speed = interface.random().value8() as isize;
-                        let decay = interface.random().value8() >> 2;
-                        let brightness = 127 + interface.random().value8() % 128;
-                        sp.reset(hue, speed, decay, brightness, 0);
-                    };
-                    for strip in 0..STRIP_NUM {
-                        let start = strip * SPARKS_PER_STRIP;
-                        let end = start + SPARKS_PER_STRIP;
-                        let speed = (start..end).map(|i| self.mono_sparks[i].speed()).max().unwrap();
-                        let decay = 0.1;
-                        self.color_sparks[strip].reset(hue + 60./360. % 1.0, decay, speed, 0);
-                    };
-                } else {
-                    let _ = interface.led_off();
-                }
-            }
-            if interface.do_next() {
-                interface.led_strip().black();
-                let _ = interface.led_off();
-                break;
+    fn is_active(&self) -> bool {
+        self.mono_sparks.iter().any(|sp| sp.is_active())
+    }
+
+    fn launch<H: Hardware>(&mut self, interface: &mut H) {
+        interface.led_on();
+        let strips = interface.led_strip().layout().strips();
+        let hue = interface.random().value();
+        for sp in self.mono_sparks[..strips * SPARKS_PER_STRIP].iter_mut() {
+            let speed = interface.random().value8() as isize;
+            let decay = interface.random().value8() >> 2;
+            let brightness = 127 + interface.random().value8() % 128;
+            sp.reset(hue, speed, decay, brightness, 0);
+        };
+        for strip in 0..strips {
+            let start = strip * SPARKS_PER_STRIP;
+            let end = start + SPARKS_PER_STRIP;
+            let speed = (start..end).map(|i| self.mono_sparks[i].speed()).max().unwrap();
+            let decay = 0.1;
+            self.color_sparks[strip].reset(hue + 60./360. % 1.0, decay, speed, 0);
+        };
+    }
+}
+
+impl<H: Hardware> Show<H> for FireWorks {
+    fn name(&self) -> &'static str {
+        "fireworks"
+    }
+
+    fn frame(&mut self, interface: &mut H, dt: Delta) {
+        interface.led_strip().black();
+        for sp in self.mono_sparks.iter_mut() {
+            sp.process(interface.led_strip(), dt);
+        }
+        for sp in self.color_sparks.iter_mut() {
+            sp.process(interface.led_strip(), dt);
+        }
+        if !self.is_active() {
+            if interface.random().value() < SPARK_PROB {
+                self.launch(interface);
+            } else {
+                interface.led_off();
             }
         }
+    }
+
+    fn on_button(&mut self, state: ButtonState, interface: &mut H) {
+        if state == ButtonState::ShortPressed && !self.is_active() {
+            self.launch(interface);
+        }
+    }
+
+    fn teardown(&mut self, _interface: &mut H) {
         for ms in self.mono_sparks.iter_mut() {
             ms.deactivate();
         }
@@ -286,50 +307,57 @@ impl FireWorks {
 
 
 const SNOW_SPARKS_PER_STRIP: usize = 2;
-const SNOW_SPARK_NUM: usize = SNOW_SPARKS_PER_STRIP * STRIP_NUM;
+const SNOW_SPARK_NUM: usize = SNOW_SPARKS_PER_STRIP * MAX_STRIPS;
 
 
 pub struct SnowSparks {
-    sparks: [FallingSparks; SNOW_SPARK_NUM]
+    sparks: [FallingSparks; SNOW_SPARK_NUM],
+    hue: f32
 }
 
 impl SnowSparks {
     pub fn new() -> SnowSparks {
         SnowSparks {
             sparks: core::array::from_fn(|i| i+1)
-                .map(|sn| FallingSparks::new(sn / SNOW_SPARKS_PER_STRIP, Color {r: 32, g: 32, b: 32}))
+                .map(|sn| FallingSparks::new(sn / SNOW_SPARKS_PER_STRIP, Color {r: 32, g: 32, b: 32})),
+            hue: 1.0
         }
     }
+}
+
+impl<H: Hardware> Show<H> for SnowSparks {
+    fn name(&self) -> &'static str {
+        "snow_sparks"
+    }
+
+    fn init(&mut self, _interface: &mut H, _params: Params) {
+        self.hue = 1.0;
+    }
 
-    pub fn show(&mut self, interface: &mut Interface) {
-        let mut hue = 1.0f32;
-        loop {
-            interface.led_strip().black();
-            for fs in self.sparks.iter_mut() {
-                let was_active = fs.is_active();
-                fs.process(&mut interface.led_strip());
-                if !fs.is_active() {
-                    if was_active {
-                        let speed = match fs.initial_speed() {
-                            0 => 192,
-                            s => (s * 4) / 5
-                        };
-                        if speed > 1 {
-                            fs.fade().reset(speed, 0);
-                        }
-                    } else if interface.random().value() < SPARK_PROB * 0.7 {
-                        fs.set_hue(hue).reset(0, STRIP_LENGTH as isize - 1);
-                        hue = (hue + 5.0/360.0) % 1.0;
+    fn frame(&mut self, interface: &mut H, dt: Delta) {
+        interface.led_strip().black();
+        let layout = interface.led_strip().layout();
+        for fs in self.sparks[..layout.strips() * SNOW_SPARKS_PER_STRIP].iter_mut() {
+            let was_active = fs.is_active();
+            fs.process(interface.led_strip(), dt);
+            if !fs.is_active() {
+                if was_active {
+                    let speed = match fs.initial_speed() {
+                        0 => 192,
+                        s => (s * 4) / 5
+                    };
+                    if speed > 1 {
+                        fs.fade().reset(speed, 0);
                     }
+                } else if interface.random().value() < SPARK_PROB * 0.7 {
+                    fs.set_hue(self.hue).reset(0, layout.length() as isize - 1);
+                    self.hue = (self.hue + 5.0/360.0) % 1.0;
                 }
             }
-            interface.write_spi();
-            if interface.do_next() {
-                interface.led_strip().black();
-                let _ = interface.led_off();
-                break;
-            }
         }
+    }
+
+    fn teardown(&mut self, _interface: &mut H) {
         for s in self.sparks.iter_mut() {
             s.deactivate();
         }
@@ -337,6 +365,8 @@ impl SnowSparks {
 }
 
 
+const SPARK_FALL_COLOR: Color = Color {r: 32, g: 0, b: 32};
+
 pub struct SparkFall {
     sparks: [FallingSparks; SPARK_NUM]
 }
@@ -345,28 +375,37 @@ impl SparkFall {
     pub fn new() -> SparkFall {
         SparkFall {
             sparks: core::array::from_fn(|i| i+1)
-                .map(|sn| FallingSparks::new(sn / SPARKS_PER_STRIP, Color {r: 32, g: 0, b: 32}))
+                .map(|sn| FallingSparks::new(sn / SPARKS_PER_STRIP, SPARK_FALL_COLOR))
 
         }
     }
+}
 
-    pub fn show(&mut self, interface: &mut Interface) {
-        loop {
-            interface.led_strip().black();
-            for fs in self.sparks.iter_mut() {
-                if !fs.is_active() && interface.random().value() < SPARK_PROB {
-                    fs.reset(0, STRIP_LENGTH as isize - 1);
-                }
-                fs.process(&mut interface.led_strip());
-            }
-            interface.delay_ms(10);
-            interface.write_spi();
-            if interface.do_next() {
-                interface.led_strip().black();
-                let _ = interface.led_off();
-                break;
+impl<H: Hardware> Show<H> for SparkFall {
+    fn name(&self) -> &'static str {
+        "spark_fall"
+    }
+
+    /// The sparks take the foreground colour.
+    fn init(&mut self, _interface: &mut H, params: Params) {
+        let color = params.colors().map_or(SPARK_FALL_COLOR, |[_, foreground]| foreground);
+        for s in self.sparks.iter_mut() {
+            s.set_color(color);
+        }
+    }
+
+    fn frame(&mut self, interface: &mut H, dt: Delta) {
+        interface.led_strip().black();
+        let layout = interface.led_strip().layout();
+        for fs in self.sparks[..layout.strips() * SPARKS_PER_STRIP].iter_mut() {
+            if !fs.is_active() && interface.random().value() < SPARK_PROB {
+                fs.reset(0, layout.length() as isize - 1);
             }
+            fs.process(interface.led_strip(), dt);
         }
+    }
+
+    fn teardown(&mut self, _interface: &mut H) {
         for s in self.sparks.iter_mut() {
             s.deactivate();
         }
@@ -385,20 +424,20 @@ impl Explosion {
         }
     }
 
-    pub fn explode(&mut self, pos: usize, explosion_hue: f32, interface: &mut Interface) {
+    pub fn explode<H: Hardware>(&mut self, pos: usize, explosion_hue: f32, interface: &mut H) {
         for s in &mut self.sparks {
             let speed = (interface.random().value8()) as isize - 128;
             s.reset(explosion_hue, speed, 64, 255, pos as isize);
         }
     }
 
-    pub fn process(&mut self, interface: &mut Interface) {
+    pub fn process<H: Hardware>(&mut self, interface: &mut H, dt: Delta) {
         if !self.is_active() {
             return;
         }
 
         for spark in self.sparks.iter_mut() {
-            spark.process(&mut interface.led_strip())
+            spark.process(interface.led_strip(), dt)
         };
     }
 
@@ -414,17 +453,17 @@ impl Explosion {
 }
 
 pub struct Explosions {
-    explosions: [Explosion; STRIP_NUM]
+    explosions: [Explosion; MAX_STRIPS]
 }
 
 impl Explosions {
     pub fn new() -> Explosions {
         Explosions {
-            explosions: core::array::from_fn(|strip_num| Explosion::new(strip_num))
+            explosions: core::array::from_fn(Explosion::new)
         }
     }
 
-    pub fn explode(&mut self, strip_num: usize, pos: usize, explosion_hue: f32, interface: &mut Interface) {
+    pub fn explode<H: Hardware>(&mut self, strip_num: usize, pos: usize, explosion_hue: f32, interface: &mut H) {
         self.explosions[strip_num].explode(pos, explosion_hue, interface)
     }
 
@@ -432,9 +471,9 @@ impl Explosions {
         !self.explosions[strip_num].is_active()
     }
 
-    pub fn process(&mut self, interface: &mut Interface) {
+    pub fn process<H: Hardware>(&mut self, interface: &mut H, dt: Delta) {
         for explosion in self.explosions.iter_mut() {
-            explosion.process(interface)
+            explosion.process(interface, dt)
         };
     }
 
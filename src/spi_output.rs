@@ -0,0 +1,80 @@
+//! APA102 output over SPI0 and SPI1, fed by DMA
+
+use embedded_hal::spi::MODE_0;
+use fugit::RateExtU32;
+use rp_pico::hal::{
+    pac,
+    dma::{Channel, CH0, CH1},
+    spi::{Spi, Enabled},
+    gpio::{self, Pin, FunctionSpi, PullDown},
+};
+
+use lumio::conf::{MAX_LEDS, SPI_BAUD_RATE};
+use lumio::ledstrip::LEDStrip;
+use lumio::output::Output;
+use lumio::protocol::Frame;
+
+use crate::dma_chain::DmaChain;
+
+type SCLK0 = Pin<gpio::bank0::Gpio6, FunctionSpi, PullDown>;
+type MOSI0 = Pin<gpio::bank0::Gpio7, FunctionSpi, PullDown>;
+
+pub type Spi0Pinout = (MOSI0, SCLK0);
+
+type SCLK1 = Pin<gpio::bank0::Gpio14, FunctionSpi, PullDown>;
+type MOSI1 = Pin<gpio::bank0::Gpio15, FunctionSpi, PullDown>;
+
+pub type Spi1Pinout = (MOSI1, SCLK1);
+
+const PERI_FEQUENCY: u32 = 450_000_000u32;
+
+/// The longest frame of a chain, the longer half of the LEDs.
+const CHAIN_LEN: usize = Frame::max_len(MAX_LEDS.div_ceil(2), 1);
+
+/// Chain 0 on SPI0, chain 1 on SPI1.  Both send their front buffer at the
+/// same time while the next frame is rendered.
+pub struct SpiOutput {
+    chain0: DmaChain<Channel<CH0>, Spi<Enabled, pac::SPI0, Spi0Pinout, 8>, u8>,
+    chain1: DmaChain<Channel<CH1>, Spi<Enabled, pac::SPI1, Spi1Pinout, 8>, u8>,
+}
+
+impl SpiOutput {
+    /// The buffers take the length of the frames of `led_strip`.
+    pub fn new(
+        spi0: pac::SPI0,
+        spi0_pins: Spi0Pinout,
+        spi1: pac::SPI1,
+        spi1_pins: Spi1Pinout,
+        dma: (Channel<CH0>, Channel<CH1>),
+        led_strip: &LEDStrip,
+        resets: &mut pac::RESETS,
+    ) -> SpiOutput {
+        let spi0 = Spi::<_, _, _, 8>::new(spi0, spi0_pins)
+            .init(resets, PERI_FEQUENCY.Hz(), SPI_BAUD_RATE.Hz(), MODE_0);
+        let spi1 = Spi::<_, _, _, 8>::new(spi1, spi1_pins)
+            .init(resets, PERI_FEQUENCY.Hz(), SPI_BAUD_RATE.Hz(), MODE_0);
+
+        let (len0, len1) = (led_strip.dump_0().len(), led_strip.dump_1().len());
+        let buffers = [
+            &mut cortex_m::singleton!(: [u8; CHAIN_LEN] = [0; CHAIN_LEN]).unwrap()[..len0],
+            &mut cortex_m::singleton!(: [u8; CHAIN_LEN] = [0; CHAIN_LEN]).unwrap()[..len0],
+            &mut cortex_m::singleton!(: [u8; CHAIN_LEN] = [0; CHAIN_LEN]).unwrap()[..len1],
+            &mut cortex_m::singleton!(: [u8; CHAIN_LEN] = [0; CHAIN_LEN]).unwrap()[..len1],
+        ];
+        let [front0, back0, front1, back1] = buffers;
+
+        SpiOutput {
+            chain0: DmaChain::new(dma.0, spi0, front0, back0),
+            chain1: DmaChain::new(dma.1, spi1, front1, back1),
+        }
+    }
+}
+
+impl Output for SpiOutput {
+    fn write(&mut self, led_strip: &LEDStrip) {
+        self.chain0.back().copy_from_slice(led_strip.dump_0());
+        self.chain1.back().copy_from_slice(led_strip.dump_1());
+        self.chain0.send();
+        self.chain1.send();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{ledstrip::LEDStrip, conf::{STRIP_LENGTH, STRIP_NUM}, led::WHITE, huewave::HueWave, interface::Interface};
+use crate::{ledstrip::LEDStrip, layout::Layout, led::WHITE, huewave::HueWave, frameclock::Delta, hardware::Hardware, show::Show};
 
 pub struct Spiral {
     start_strip: usize,
@@ -14,65 +14,73 @@ impl Spiral {
         self.steps = 0;
     }
 
-    pub fn swirl(&mut self) {
-        self.steps = (self.steps + 1) % (STRIP_LENGTH * 2);
+    pub fn swirl(&mut self, layout: Layout) {
+        self.steps = (self.steps + 1) % (layout.length() * 2);
     }
 
-    pub fn step(&mut self) {
-        self.steps = STRIP_LENGTH;
-        self.start_strip = (self.start_strip + STRIP_NUM - 1) % STRIP_NUM
+    pub fn step(&mut self, layout: Layout) {
+        self.steps = layout.length();
+        self.start_strip = (self.start_strip + layout.strips() - 1) % layout.strips()
     }
 
     pub fn process(&mut self, led_strip: &mut LEDStrip) {
-        let start = (self.steps / STRIP_LENGTH) * (self.steps % STRIP_LENGTH);
-        let mut strip = (self.start_strip + start) % STRIP_NUM;
-        let target = self.steps.min(STRIP_LENGTH);
+        let layout = led_strip.layout();
+        let start = (self.steps / layout.length()) * (self.steps % layout.length());
+        let mut strip = layout.neighbour(self.start_strip, start as isize);
+        let target = self.steps.min(layout.length());
         for i in start..target {
-            let pos = (strip*STRIP_LENGTH + i) as isize;
-            led_strip.set_led(pos, WHITE);
-            strip = (strip+1) % STRIP_NUM;
+            let Some(s) = strip else {
+                break
+            };
+            led_strip.set_led(layout.index(s, i) as isize, WHITE);
+            strip = layout.neighbour(s, 1);
         }
     }
 }
 
 
+enum Motion {
+    Lift,
+    Swirl
+}
+
 pub struct HueSpiral {
     spiral: Spiral,
-    huewave: HueWave
+    huewave: HueWave,
+    motion: Motion
 }
 
 impl HueSpiral {
-    pub fn new() -> HueSpiral {
-        HueSpiral { spiral: Spiral::new(0), huewave: HueWave::new() }
+    pub fn new_lift() -> HueSpiral {
+        HueSpiral { spiral: Spiral::new(0), huewave: HueWave::new(), motion: Motion::Lift }
     }
 
-    pub fn show_lift(&mut self, interface: &mut Interface) {
-        loop {
-            self.huewave.process(&mut interface.led_strip());
-            self.spiral.process(&mut interface.led_strip());
-            self.spiral.step();
+    pub fn new_swirl() -> HueSpiral {
+        HueSpiral { spiral: Spiral::new(0), huewave: HueWave::new(), motion: Motion::Swirl }
+    }
+}
 
-            interface.write_spi();
-            if interface.do_next() {
-                interface.led_strip().black();
-                break;
-            }
-//            interface.delay_ms(50);
+impl<H: Hardware> Show<H> for HueSpiral {
+    fn name(&self) -> &'static str {
+        match self.motion {
+            Motion::Lift => "spiral_lift",
+            Motion::Swirl => "spiral_swirl"
         }
-        self.spiral.reset();
     }
 
-    pub fn show_swirl(&mut self, interface: &mut Interface) {
-        loop {
-            self.huewave.process(&mut interface.led_strip());
-            self.spiral.process(&mut interface.led_strip());
-            self.spiral.swirl();
+    fn frame(&mut self, interface: &mut H, _dt: Delta) {
+        self.huewave.process(interface.led_strip());
+        self.spiral.process(interface.led_strip());
+        let layout = interface.led_strip().layout();
+        match self.motion {
+            Motion::Lift => self.spiral.step(layout),
+            Motion::Swirl => self.spiral.swirl(layout)
+        }
+    }
 
-            interface.write_spi();
-            if interface.do_next() {
-                interface.led_strip().black();
-                break;
-            }
+    fn teardown(&mut self, _interface: &mut H) {
+        if let Motion::Lift = self.motion {
+            self.spiral.reset();
         }
     }
 }
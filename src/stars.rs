@@ -1,16 +1,21 @@
-use crate::{random, conf::NUM_LED, conf::STRIP_NUM, ledstrip::LEDStrip, led::Color, interface::Interface};
+use crate::{random, ledstrip::LEDStrip, led::Color, frameclock::Delta, hardware::Hardware, show::{Params, Show}};
 
 const NOVA_PROB: u8 = 3;
 
 pub struct Stars {
+    name: &'static str,
+    /// The colours of the sky and the stars unless the playlist sets them.
+    colors: [Color; 2],
     sky_color: Color,
     star_color: Color,
     random: random::Random
 }
 
 impl Stars {
-    pub fn new(sky_color: Color, star_color: Color) -> Stars {
+    pub fn new(name: &'static str, sky_color: Color, star_color: Color) -> Stars {
         Stars {
+            name,
+            colors: [sky_color, star_color],
             sky_color,
             star_color,
             random: random::Random::new(4023749823)
@@ -18,31 +23,33 @@ impl Stars {
     }
 
     pub fn reset(&mut self, led_strip: &mut LEDStrip) {
-        for i in 0..NUM_LED {
+        for i in 0..led_strip.layout().num_leds() {
             led_strip.set_led(i as isize, self.sky_color);
         }
     }
 
     pub fn process(&mut self, led_strip: &mut LEDStrip) {
-        let nova_prob = NOVA_PROB * STRIP_NUM as u8;
+        let layout = led_strip.layout();
+        let nova_prob = NOVA_PROB.saturating_mul(layout.strips() as u8);
         if self.random.value8() < nova_prob {
-            let pos = self.random.value32(NUM_LED as u32) as usize;
+            let pos = self.random.value32(layout.num_leds() as u32) as usize;
             led_strip.set_led(pos as isize, self.star_color);
             led_strip.led_mut(pos).set_target_flickering(self.sky_color, 2, 96);
         }
     }
+}
 
-    pub fn show(&mut self, interface: &mut Interface) {
-        self.reset(&mut interface.led_strip());
-        loop {
-            self.process(&mut interface.led_strip());
-            interface.write_spi();
+impl<H: Hardware> Show<H> for Stars {
+    fn name(&self) -> &'static str {
+        self.name
+    }
 
-            if interface.do_next() {
-                interface.led_strip().black();
-                break;
-            }
-        }
+    fn init(&mut self, interface: &mut H, params: Params) {
+        [self.sky_color, self.star_color] = params.colors().unwrap_or(self.colors);
+        self.reset(interface.led_strip());
     }
 
+    fn frame(&mut self, interface: &mut H, _dt: Delta) {
+        self.process(interface.led_strip());
+    }
 }
@@ -0,0 +1,454 @@
+//! Frames streamed live by a host over USB
+//!
+//! Besides command lines the host may send whole frames, in the Adalight or
+//! the TPM2 format, with the colours of the LEDs in the order of their
+//! `LEDStrip` index:
+//!
+//! ```text
+//! Adalight: 'A' 'd' 'a' count-1 (u16, big endian) checksum (hi ^ lo ^ 0x55) RGB...
+//! TPM2:     0xc9 0xda size (u16, big endian) RGB... 0x36
+//! ```
+//!
+//! TPM2 packets of other types are skipped.  Frames may also come compressed,
+//! starting with 0xd5, see `upload`.  The bytes around the frames are passed
+//! on as text.  After the `opc` command, with `\n` at its end, everything is
+//! taken as Open Pixel Control messages, see `opc`, until `reset()`.
+
+use crate::command::Command;
+use crate::conf::MAX_LEDS;
+use crate::layout::Layout;
+use crate::led::{Color, BLACK};
+use crate::ledstrip::LEDStrip;
+use crate::opc::OpcDecoder;
+use crate::text::{Line, Text};
+use crate::upload::{Reply, UploadDecoder, START as UPLOAD_START};
+
+const ADALIGHT_MAGIC: &[u8] = b"Ada";
+const TPM2_START: u8 = 0xc9;
+const TPM2_DATA: u8 = 0xda;
+const TPM2_END: u8 = 0x36;
+/// Replies to uploads that are not yet sent.
+const REPLIES: usize = 32;
+
+/// A frame from the host, the LEDs beyond it keep their colour.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct LiveFrame {
+    colors: [Color; MAX_LEDS],
+    len: usize
+}
+
+impl LiveFrame {
+    pub const fn new() -> LiveFrame {
+        LiveFrame { colors: [BLACK; MAX_LEDS], len: 0 }
+    }
+
+    /// A black frame of `len` LEDs.
+    pub const fn black(len: usize) -> LiveFrame {
+        assert!(len <= MAX_LEDS);
+        LiveFrame { colors: [BLACK; MAX_LEDS], len }
+    }
+
+    /// Makes this a black frame of `len` LEDs, in place.
+    pub fn clear(&mut self, len: usize) {
+        self.len = len.min(MAX_LEDS);
+        self.colors[..self.len].fill(BLACK);
+    }
+
+    /// Sets LED `index`, if it is part of the frame.
+    pub fn set(&mut self, index: usize, color: Color) {
+        if index < self.len {
+            self.colors[index] = color;
+        }
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors[..self.len]
+    }
+
+    /// Puts the frame onto `led_strip`.
+    pub fn write_to(&self, led_strip: &mut LEDStrip) {
+        let num_leds = led_strip.layout().num_leds();
+        for (i, &color) in self.colors().iter().take(num_leds).enumerate() {
+            led_strip.set_led(i as isize, color);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    Text,
+    /// The first bytes of `ADALIGHT_MAGIC` are in.
+    Magic(usize),
+    /// The bytes of the Adalight header after the magic.
+    AdalightHeader([u8; 3], usize),
+    Tpm2Type,
+    /// The type and the bytes of the size.
+    Tpm2Size(u8, [u8; 2], usize),
+    /// `len` bytes of colours, `end` is the TPM2 end byte that follows.
+    Pixels { len: usize, received: usize, end: bool },
+    /// A TPM2 packet that is not a frame, then the end byte.
+    Skip(usize),
+    /// The TPM2 end byte, after a frame if `frame`.
+    Tpm2End { frame: bool },
+    /// A compressed frame.
+    Upload
+}
+
+/// Picks the frames out of the bytes received, they may come in any pieces.
+///
+/// The formats share two frames: Adalight, TPM2 and uploads decode into
+/// `receiving` and copy a complete frame to `frame`, OPC sets the pixels of
+/// `frame` directly.
+pub struct StreamDecoder {
+    state: State,
+    receiving: LiveFrame,
+    channels: [u8; 3],
+    /// The latest complete frame.
+    frame: LiveFrame,
+    /// Whether `frame` changed since it was taken.
+    fresh: bool,
+    /// The text line so far, to notice the `opc` command.
+    line: Line,
+    opc: OpcDecoder,
+    /// Whether the host switched to OPC.
+    opc_mode: bool,
+    upload: UploadDecoder,
+    replies: Text<REPLIES>
+}
+
+impl StreamDecoder {
+    pub const fn new(layout: Layout) -> StreamDecoder {
+        StreamDecoder {
+            state: State::Text,
+            receiving: LiveFrame::new(),
+            channels: [0; 3],
+            frame: LiveFrame::new(),
+            fresh: false,
+            line: Line::new(),
+            opc: OpcDecoder::new(layout),
+            opc_mode: false,
+            upload: UploadDecoder::new(),
+            replies: Text::new()
+        }
+    }
+
+    /// Back to frames and text, e.g. when the host closes the port.
+    pub fn reset(&mut self) {
+        self.state = State::Text;
+        self.line.clear();
+        self.opc_mode = false;
+    }
+
+    /// Decodes `bytes`, what is not part of a frame goes to `text`.
+    pub fn push(&mut self, bytes: &[u8], mut text: impl FnMut(&[u8])) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            if self.opc_mode {
+                self.opc.push(&bytes[i..], &mut self.frame);
+                self.fresh |= self.opc.take_changed();
+                return;
+            }
+            self.decode(byte, &mut text);
+        }
+    }
+
+    /// The replies to the uploads so far, to be sent to the host.
+    pub fn take_replies(&mut self) -> Text<REPLIES> {
+        core::mem::replace(&mut self.replies, Text::new())
+    }
+
+    /// Whether a frame came that was not taken yet.
+    pub fn has_frame(&self) -> bool {
+        self.fresh
+    }
+
+    /// The latest complete frame, once.
+    pub fn take_frame(&mut self) -> Option<&LiveFrame> {
+        core::mem::take(&mut self.fresh).then_some(&self.frame)
+    }
+
+    fn decode(&mut self, byte: u8, text: &mut impl FnMut(&[u8])) {
+        self.state = match self.state {
+            State::Text if byte == TPM2_START => State::Tpm2Type,
+            State::Text if byte == ADALIGHT_MAGIC[0] => State::Magic(1),
+            State::Text if byte == UPLOAD_START => {
+                self.upload.begin();
+                State::Upload
+            }
+            State::Text => {
+                self.pass_text(&[byte], text);
+                State::Text
+            }
+            State::Magic(n) if byte == ADALIGHT_MAGIC[n] => {
+                if n + 1 == ADALIGHT_MAGIC.len() { State::AdalightHeader([0; 3], 0) } else { State::Magic(n + 1) }
+            }
+            State::Magic(n) => {
+                // Not a frame after all
+                self.pass_text(&ADALIGHT_MAGIC[..n], text);
+                self.state = State::Text;
+                return self.decode(byte, text);
+            }
+            State::AdalightHeader(mut header, n) => {
+                header[n] = byte;
+                if n < 2 {
+                    State::AdalightHeader(header, n + 1)
+                } else if header[0] ^ header[1] ^ 0x55 == header[2] {
+                    let count = u16::from_be_bytes([header[0], header[1]]) as usize + 1;
+                    self.begin_frame(3 * count, false)
+                } else {
+                    State::Text
+                }
+            }
+            State::Tpm2Type => State::Tpm2Size(byte, [0; 2], 0),
+            State::Tpm2Size(kind, mut size, n) => {
+                size[n] = byte;
+                if n == 0 {
+                    State::Tpm2Size(kind, size, 1)
+                } else {
+                    let len = u16::from_be_bytes(size) as usize;
+                    match kind {
+                        TPM2_DATA => self.begin_frame(len, true),
+                        _ if len == 0 => State::Tpm2End { frame: false },
+                        _ => State::Skip(len)
+                    }
+                }
+            }
+            State::Pixels { len, received, end } => {
+                self.channels[received % 3] = byte;
+                let led = received / 3;
+                if received % 3 == 2 && led < MAX_LEDS {
+                    let [r, g, b] = self.channels;
+                    self.receiving.colors[led] = Color { r, g, b };
+                }
+                if received + 1 < len {
+                    State::Pixels { len, received: received + 1, end }
+                } else if end {
+                    State::Tpm2End { frame: true }
+                } else {
+                    self.finish_frame();
+                    State::Text
+                }
+            }
+            State::Skip(len) => if len > 1 { State::Skip(len - 1) } else { State::Tpm2End { frame: false } },
+            State::Tpm2End { frame } => {
+                if frame && byte == TPM2_END && self.receiving.len > 0 {
+                    self.finish_frame();
+                }
+                State::Text
+            }
+            State::Upload => match self.upload.decode(byte, &mut self.receiving, &mut self.frame) {
+                Some(reply) => {
+                    self.replies.push(&reply.to_bytes());
+                    self.fresh |= matches!(reply, Reply::Ack(_));
+                    State::Text
+                }
+                None => State::Upload
+            }
+        }
+    }
+
+    /// Passes `bytes` on to `text` and switches to OPC after its command.
+    fn pass_text(&mut self, bytes: &[u8], text: &mut impl FnMut(&[u8])) {
+        text(bytes);
+        for &byte in bytes {
+            // Only `\n` ends the line here, after `\r\n` it would be taken
+            // for the start of a message
+            match byte {
+                b'\r' => continue,
+                b'\n' => {}
+                _ => {
+                    self.line.push(&[byte]);
+                    continue;
+                }
+            }
+            if Command::parse(self.line.as_bytes()) == Ok(Command::Opc) {
+                self.opc.reset(&mut self.frame);
+                self.upload.forget();
+                self.opc_mode = true;
+            }
+            self.line.clear();
+        }
+    }
+
+    /// Takes over the Adalight or TPM2 frame received.
+    fn finish_frame(&mut self) {
+        self.frame = self.receiving;
+        self.fresh = true;
+        self.upload.forget();
+    }
+
+    fn begin_frame(&mut self, len: usize, end: bool) -> State {
+        self.receiving.len = (len / 3).min(MAX_LEDS);
+        match (len, end) {
+            (0, true) => State::Tpm2End { frame: true },
+            (0, false) => State::Text,
+            _ => State::Pixels { len, received: 0, end }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::{RED, WHITE};
+    use crate::upload::{crc16, ACK, KEY, LITERAL, NAK};
+    use proptest::prelude::*;
+
+    fn adalight(colors: &[Color]) -> Vec<u8> {
+        let [hi, lo] = (colors.len() as u16 - 1).to_be_bytes();
+        let mut bytes = vec![b'A', b'd', b'a', hi, lo, hi ^ lo ^ 0x55];
+        bytes.extend(colors.iter().flat_map(|c| [c.r, c.g, c.b]));
+        bytes
+    }
+
+    fn tpm2(kind: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![TPM2_START, kind];
+        bytes.extend((payload.len() as u16).to_be_bytes());
+        bytes.extend(payload);
+        bytes.push(TPM2_END);
+        bytes
+    }
+
+    fn upload(colors: &[Color]) -> Vec<u8> {
+        let mut bytes = vec![KEY, 1];
+        bytes.extend((colors.len() as u16).to_be_bytes());
+        bytes.extend((1 + 3 * colors.len() as u16).to_be_bytes());
+        bytes.push(LITERAL | (colors.len() as u8 - 1));
+        bytes.extend(colors.iter().flat_map(|c| [c.r, c.g, c.b]));
+        bytes.extend(crc16(0xffff, &bytes).to_be_bytes());
+        bytes.insert(0, UPLOAD_START);
+        bytes
+    }
+
+    /// The frames and the text decoded from `bytes`, pushed in pieces of
+    /// `chunk` bytes.
+    fn decode(bytes: &[u8], chunk: usize) -> (Vec<Vec<Color>>, Vec<u8>) {
+        let mut decoder = StreamDecoder::new(Layout::new(2, 2, true));
+        let mut frames = Vec::new();
+        let mut text = Vec::new();
+        for piece in bytes.chunks(chunk) {
+            decoder.push(piece, |bytes| text.extend_from_slice(bytes));
+            if let Some(frame) = decoder.take_frame() {
+                frames.push(frame.colors().to_vec());
+            }
+        }
+        (frames, text)
+    }
+
+    #[test]
+    fn adalight_frames_decode() {
+        let (frames, text) = decode(&adalight(&[RED, WHITE, BLACK]), 100);
+        assert!(frames == [vec![RED, WHITE, BLACK]]);
+        assert!(text.is_empty());
+    }
+
+    #[test]
+    fn tpm2_frames_decode() {
+        let (frames, _) = decode(&tpm2(TPM2_DATA, &[255, 0, 0, 1, 2, 3]), 100);
+        assert!(frames == [vec![RED, Color { r: 1, g: 2, b: 3 }]]);
+    }
+
+    #[test]
+    fn other_tpm2_packets_are_skipped() {
+        let mut bytes = tpm2(0xc0, &[1, 2]);
+        bytes.extend(b"status\n");
+        let (frames, text) = decode(&bytes, 100);
+        assert!(frames.is_empty());
+        assert_eq!(text, b"status\n");
+    }
+
+    #[test]
+    fn packets_after_a_frame_do_not_repeat_it() {
+        let mut bytes = tpm2(TPM2_DATA, &[255, 0, 0]);
+        bytes.extend(tpm2(0xc0, &[0x42]));
+        bytes.extend(tpm2(0xaa, &[]));
+        let (frames, _) = decode(&bytes, 1);
+        assert!(frames == [vec![RED]]);
+    }
+
+    #[test]
+    fn refused_uploads_are_not_shown_by_other_packets() {
+        let mut bytes = upload(&[RED, WHITE]);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        bytes.extend(tpm2(0xc0, &[]));
+        let (frames, _) = decode(&bytes, 100);
+        assert!(frames.is_empty());
+    }
+
+    #[test]
+    fn uploads_too_long_for_their_count_do_not_swallow_text() {
+        let mut bytes = vec![UPLOAD_START, KEY, 1, 0, 1, 0xff, 0xff];
+        bytes.extend(b"next\n");
+        let mut decoder = StreamDecoder::new(Layout::new(2, 2, true));
+        let mut text = Vec::new();
+        decoder.push(&bytes, |bytes| text.extend_from_slice(bytes));
+        assert_eq!(text, b"next\n");
+        assert_eq!(decoder.take_replies().as_bytes(), [NAK, 1]);
+    }
+
+    #[test]
+    fn text_that_looks_like_a_frame_is_passed_on() {
+        let (frames, text) = decode(b"select Adx\nAAd", 100);
+        assert!(frames.is_empty());
+        assert_eq!(text, b"select Adx\nA");
+    }
+
+    #[test]
+    fn bad_checksums_drop_the_header() {
+        let mut bytes = adalight(&[RED]);
+        bytes[5] ^= 1;
+        let (frames, text) = decode(&bytes, 100);
+        assert!(frames.is_empty());
+        assert_eq!(text, [255, 0, 0]);
+    }
+
+    #[test]
+    fn frames_longer_than_the_strip_are_cut() {
+        let (frames, _) = decode(&adalight(&[WHITE; MAX_LEDS + 2]), 1000);
+        assert_eq!(frames[0].len(), MAX_LEDS);
+        assert!(frames[0].iter().all(|&c| c == WHITE));
+    }
+
+    #[test]
+    fn uploads_are_answered() {
+        let mut decoder = StreamDecoder::new(Layout::new(2, 2, true));
+        let mut bytes = upload(&[RED, WHITE]);
+        bytes.extend(upload(&[WHITE]));
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        decoder.push(&bytes, |_| panic!("no text"));
+        assert!(decoder.take_frame().unwrap().colors() == [RED, WHITE]);
+        assert_eq!(decoder.take_replies().as_bytes(), [ACK, 1, NAK, 1]);
+        assert!(decoder.take_replies().is_empty());
+    }
+
+    #[test]
+    fn the_opc_command_switches_to_opc_until_reset() {
+        let mut decoder = StreamDecoder::new(Layout::new(2, 2, true));
+        let mut text = Vec::new();
+        let mut bytes = b"status\nopc\r\n".to_vec();
+        bytes.extend([1, 0, 0, 3, 255, 0, 0]);
+        decoder.push(&bytes, |bytes| text.extend_from_slice(bytes));
+        assert_eq!(text, b"status\nopc\r\n");
+        assert!(decoder.take_frame().unwrap().colors() == [RED, BLACK, BLACK, BLACK]);
+
+        decoder.reset();
+        decoder.push(b"next\n", |bytes| text.extend_from_slice(bytes));
+        assert_eq!(text, b"status\nopc\r\nnext\n");
+        assert!(decoder.take_frame().is_none());
+    }
+
+    proptest! {
+        #[test]
+        fn frames_and_text_survive_any_split(chunk in 1usize..20) {
+            let mut bytes = b"next\n".to_vec();
+            bytes.extend(adalight(&[RED; 5]));
+            bytes.extend(b"status\n");
+            bytes.extend(tpm2(TPM2_DATA, &[255; 9]));
+            bytes.extend(upload(&[RED, WHITE]));
+            let (frames, text) = decode(&bytes, chunk);
+            prop_assert!(frames == [vec![RED; 5], vec![WHITE; 3], vec![RED, WHITE]]);
+            prop_assert_eq!(text, b"next\nstatus\n".to_vec());
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Formatting text for the log without an allocator
+
+use core::fmt;
+
+const LINE_LENGTH: usize = 96;
+
+/// Up to `N` bytes of text, formatted with `write!`.  What does not fit is
+/// cut off.
+#[derive(Clone)]
+pub struct Text<const N: usize> {
+    bytes: [u8; N],
+    len: usize
+}
+
+/// A line of text.
+pub type Line = Text<LINE_LENGTH>;
+
+impl<const N: usize> Text<N> {
+    pub const fn new() -> Text<N> {
+        Text { bytes: [0; N], len: 0 }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends `bytes`, returns whether all of them fit.
+    pub fn push(&mut self, bytes: &[u8]) -> bool {
+        let len = bytes.len().min(N - self.len);
+        self.bytes[self.len..self.len + len].copy_from_slice(&bytes[..len]);
+        self.len += len;
+        len == bytes.len()
+    }
+
+    /// Drops the first `len` bytes, e.g. once they are sent.
+    pub fn consume(&mut self, len: usize) {
+        let len = len.min(self.len);
+        self.bytes.copy_within(len..self.len, 0);
+        self.len -= len;
+    }
+
+    /// Keeps the first `len` bytes.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl<const N: usize> fmt::Write for Text<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.push(s.as_bytes()) { Ok(()) } else { Err(fmt::Error) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn formats_into_the_line() {
+        let mut line = Line::new();
+        writeln!(line, "feed {} wants {} mA", 1, 4200).unwrap();
+        assert_eq!(line.as_bytes(), b"feed 1 wants 4200 mA\n");
+    }
+
+    #[test]
+    fn long_lines_are_cut_off() {
+        let mut line = Line::new();
+        assert!(write!(line, "{:200}", "x").is_err());
+        assert_eq!(line.as_bytes().len(), LINE_LENGTH);
+    }
+
+    #[test]
+    fn pushes_bytes() {
+        let mut line = Line::new();
+        assert!(line.push(b"snake"));
+        assert!(line.push(b"\n"));
+        assert_eq!(line.as_bytes(), b"snake\n");
+        assert!(!line.push(&[b'x'; LINE_LENGTH]));
+        assert_eq!(line.as_bytes().len(), LINE_LENGTH);
+    }
+
+    #[test]
+    fn consumes_from_the_front() {
+        let mut text = Text::<8>::new();
+        text.push(b"ok\nsnow");
+        text.consume(3);
+        assert_eq!(text.as_bytes(), b"snow");
+        assert!(text.push(b"1234"));
+        text.consume(100);
+        assert!(text.is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+//! Blending from one show into the next
+//!
+//! When a show ends, the LED strip keeps its last frame.  During the
+//! `Transition` of the next playlist entry the frames of the new show are
+//! mixed with it on their way out, the shows themselves don't notice.
+
+use fugit::MicrosDurationU64;
+
+use crate::layout::Layout;
+use crate::led::{Color, BLACK};
+
+/// Width of the soft edge of the wipes, in 1/256 of the way.
+const EDGE: u32 = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Blend {
+    /// A hard cut, the new show starts on black.
+    Cut,
+    Crossfade,
+    FadeThroughBlack,
+    /// The new show spreads around the strips and up at the same time.
+    SpiralWipe,
+    /// The new show rises from the bottom of the strips.
+    WipeUp
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Transition {
+    blend: Blend,
+    time: MicrosDurationU64
+}
+
+impl Transition {
+    pub const fn new(blend: Blend, time: MicrosDurationU64) -> Transition {
+        Transition { blend, time }
+    }
+
+    pub const fn cut() -> Transition {
+        Transition::new(Blend::Cut, MicrosDurationU64::micros(0))
+    }
+
+    pub const fn blend(&self) -> Blend {
+        self.blend
+    }
+
+    pub const fn time(&self) -> MicrosDurationU64 {
+        self.time
+    }
+
+    /// How far the transition is `elapsed` into the new show, `None` once it
+    /// is over.
+    pub fn mix(&self, elapsed: MicrosDurationU64) -> Option<Mix> {
+        if self.blend == Blend::Cut || elapsed >= self.time {
+            return None;
+        }
+        let progress = elapsed.to_micros() * 256 / self.time.to_micros();
+        Some(Mix { blend: self.blend, progress: progress as u8 })
+    }
+}
+
+/// A transition at one point in time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Mix {
+    blend: Blend,
+    /// From 0, all of the old frame, to 255, almost all of the new one.
+    progress: u8
+}
+
+impl Mix {
+    pub const fn new(blend: Blend, progress: u8) -> Mix {
+        Mix { blend, progress }
+    }
+
+    /// The colour of LED `index` of `layout`, `old` in the last frame of the
+    /// previous show and `new` in the current one.
+    pub fn apply(&self, layout: &Layout, index: usize, old: Color, new: Color) -> Color {
+        let progress = self.progress as u32;
+        let strip = (index / layout.length()) as u32;
+        let y = (index % layout.length()) as u32;
+        match self.blend {
+            Blend::Cut => new,
+            Blend::Crossfade => lerp(old, new, progress),
+            Blend::FadeThroughBlack => {
+                if progress <= 128 {
+                    lerp(old, BLACK, (progress * 2).min(255))
+                } else {
+                    lerp(BLACK, new, (progress - 128) * 255 / 127)
+                }
+            }
+            Blend::SpiralWipe => {
+                let around = strip * 256 / layout.strips() as u32;
+                let up = y * 256 / layout.length() as u32;
+                lerp(old, new, edge(progress, (around + up) / 2))
+            }
+            Blend::WipeUp => lerp(old, new, edge(progress, y * 256 / layout.length() as u32))
+        }
+    }
+}
+
+/// How far the soft edge of a wipe at `progress` has passed `threshold`.
+fn edge(progress: u32, threshold: u32) -> u32 {
+    let front = progress * (256 + EDGE) / 256;
+    (front.saturating_sub(threshold) * 255 / EDGE).min(255)
+}
+
+/// From `a` at 0 to `b` at 255.
+fn lerp(a: Color, b: Color, amount: u32) -> Color {
+    let channel = |a: u8, b: u8| ((a as u32 * (255 - amount) + b as u32 * amount) / 255) as u8;
+    Color { r: channel(a.r, b.r), g: channel(a.g, b.g), b: channel(a.b, b.b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::{RED, WHITE};
+    use proptest::prelude::*;
+
+    const BLENDS: [Blend; 5] = [Blend::Cut, Blend::Crossfade, Blend::FadeThroughBlack, Blend::SpiralWipe, Blend::WipeUp];
+
+    fn fade(blend: Blend) -> Transition {
+        Transition::new(blend, MicrosDurationU64::millis(1_000))
+    }
+
+    proptest! {
+        #[test]
+        fn transitions_start_on_the_old_frame(blend in 1usize..5, index in 0..Layout::FLOWER.num_leds()) {
+            let mix = fade(BLENDS[blend]).mix(MicrosDurationU64::micros(0)).unwrap();
+            prop_assert!(mix.apply(&Layout::FLOWER, index, RED, WHITE) == RED);
+        }
+
+        #[test]
+        fn transitions_end_on_the_new_frame(blend in 1usize..5, index in 0..Layout::FLOWER.num_leds()) {
+            let mix = Mix::new(BLENDS[blend], 255);
+            prop_assert!(mix.apply(&Layout::FLOWER, index, RED, WHITE) == WHITE);
+        }
+    }
+
+    #[test]
+    fn transitions_are_over_after_their_time() {
+        let transition = fade(Blend::Crossfade);
+        assert!(transition.mix(MicrosDurationU64::millis(999)).is_some());
+        assert_eq!(transition.mix(MicrosDurationU64::millis(1_000)), None);
+        assert_eq!(Transition::cut().mix(MicrosDurationU64::micros(0)), None);
+    }
+
+    #[test]
+    fn fade_through_black_is_black_half_way() {
+        let mix = fade(Blend::FadeThroughBlack).mix(MicrosDurationU64::millis(500)).unwrap();
+        assert!(mix.apply(&Layout::FLOWER, 0, RED, WHITE) == BLACK);
+    }
+
+    #[test]
+    fn crossfade_mixes_evenly() {
+        let mix = fade(Blend::Crossfade).mix(MicrosDurationU64::millis(500)).unwrap();
+        let color = mix.apply(&Layout::FLOWER, 0, BLACK, WHITE);
+        assert_eq!((color.r, color.g, color.b), (128, 128, 128));
+    }
+
+    #[test]
+    fn wipe_up_starts_at_the_bottom() {
+        let layout = Layout::FLOWER;
+        let mix = fade(Blend::WipeUp).mix(MicrosDurationU64::millis(500)).unwrap();
+        for strip in 0..layout.strips() {
+            assert!(mix.apply(&layout, layout.index(strip, 0), RED, WHITE) == WHITE);
+            assert!(mix.apply(&layout, layout.index(strip, 59), RED, WHITE) == RED);
+        }
+    }
+
+    #[test]
+    fn spiral_wipe_goes_around_and_up() {
+        let layout = Layout::FLOWER;
+        let mix = fade(Blend::SpiralWipe).mix(MicrosDurationU64::millis(300)).unwrap();
+        assert!(mix.apply(&layout, layout.index(0, 0), RED, WHITE) == WHITE);
+        assert!(mix.apply(&layout, layout.index(12, 30), RED, WHITE) == RED);
+        assert!(mix.apply(&layout, layout.index(23, 0), RED, WHITE) == RED);
+    }
+}
@@ -0,0 +1,364 @@
+//! Compressed frames uploaded by a host
+//!
+//! A full frame in the Adalight or TPM2 format takes 3 bytes per LED.  This
+//! format sends what changed since the frame before, in runs:
+//!
+//! ```text
+//! 0xd5 flags sequence (u8) count (u16) length (u16) ops... crc (u16)
+//! ```
+//!
+//! All numbers are big endian.  `count` is the number of LEDs of the frame,
+//! `length` the number of bytes of the ops, the CRC is CRC-16/CCITT-FALSE of
+//! everything between `0xd5` and itself.  Each op starts with a byte, its two
+//! top bits say what it does to the next n LEDs, its lower six bits are n - 1:
+//!
+//! ```text
+//! 00 keep:    n LEDs keep their colour
+//! 01 run:     RGB, n LEDs take it
+//! 10 literal: n times RGB
+//! 11 shift:   n signed bytes, each added to all channels of its LED
+//! ```
+//!
+//! The LEDs after the last op keep their colour.  A key frame (flag bit 0)
+//! starts from black, any other frame from the one uploaded before, it needs
+//! the next sequence number and the same count.  Every packet is answered with
+//! `0x06 sequence` if the frame was taken, with `0x15 sequence` if not, upon
+//! which the host sends a key frame.  Neither byte appears in the log.  A
+//! header with more than `MAX_LEDS` LEDs or more than 4 bytes of ops per LED
+//! is answered right away, the bytes after it are taken for text again.
+
+use crate::conf::MAX_LEDS;
+use crate::led::Color;
+use crate::stream::LiveFrame;
+
+pub const START: u8 = 0xd5;
+/// The flag of key frames.
+pub const KEY: u8 = 0x01;
+pub const KEEP: u8 = 0x00;
+pub const RUN: u8 = 0x40;
+pub const LITERAL: u8 = 0x80;
+pub const SHIFT: u8 = 0xc0;
+/// The most LEDs a single op covers.
+pub const MAX_OP_LEDS: usize = 64;
+pub const ACK: u8 = 0x06;
+pub const NAK: u8 = 0x15;
+
+/// CRC-16/CCITT-FALSE of `bytes`, continuing from `crc`, start with `0xffff`.
+pub fn crc16(mut crc: u16, bytes: &[u8]) -> u16 {
+    for &byte in bytes {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// The answer to a packet.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reply {
+    Ack(u8),
+    Nak(u8)
+}
+
+impl Reply {
+    pub fn to_bytes(self) -> [u8; 2] {
+        match self {
+            Reply::Ack(sequence) => [ACK, sequence],
+            Reply::Nak(sequence) => [NAK, sequence]
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    /// The bytes of the header after the start byte.
+    Header([u8; 6], usize),
+    Op,
+    /// An op of `n` LEDs, `received` bytes of its data are in.
+    Data { op: u8, n: usize, received: usize },
+    Crc([u8; 2], usize)
+}
+
+/// Decodes the packets after their start byte, which may come in any pieces.
+/// The frame of a packet is put together in a buffer and taken over into the
+/// frame of the caller once the packet is complete.
+pub struct UploadDecoder {
+    state: State,
+    crc: u16,
+    sequence: u8,
+    /// The bytes of ops still to come.
+    left: usize,
+    /// The next LED the ops set.
+    led: usize,
+    /// Whether nothing was wrong with the packet so far.
+    ok: bool,
+    channels: [u8; 3],
+    /// The sequence number of the frame taken last.
+    last: Option<u8>
+}
+
+impl UploadDecoder {
+    pub const fn new() -> UploadDecoder {
+        UploadDecoder {
+            state: State::Header([0; 6], 0),
+            crc: 0xffff,
+            sequence: 0,
+            left: 0,
+            led: 0,
+            ok: true,
+            channels: [0; 3],
+            last: None
+        }
+    }
+
+    /// Starts a packet, its start byte is in.
+    pub fn begin(&mut self) {
+        self.state = State::Header([0; 6], 0);
+        self.crc = 0xffff;
+        self.ok = true;
+    }
+
+    /// The frame was set some other way, the next one has to be a key frame.
+    pub fn forget(&mut self) {
+        self.last = None;
+    }
+
+    /// Decodes the next byte of the packet into `receiving`, returns the reply
+    /// once it is over.  A frame that is taken is copied to `frame`, which a
+    /// delta frame starts from.
+    pub fn decode(&mut self, byte: u8, receiving: &mut LiveFrame, frame: &mut LiveFrame) -> Option<Reply> {
+        if !matches!(self.state, State::Crc(..)) {
+            self.crc = crc16(self.crc, &[byte]);
+        }
+        if matches!(self.state, State::Op | State::Data { .. }) {
+            self.left -= 1;
+        }
+        self.state = match self.state {
+            State::Header(mut header, n) => {
+                header[n] = byte;
+                if n < 5 {
+                    State::Header(header, n + 1)
+                } else {
+                    match self.begin_frame(header, receiving, frame) {
+                        Ok(state) => state,
+                        Err(reply) => {
+                            self.state = State::Header([0; 6], 0);
+                            return Some(reply);
+                        }
+                    }
+                }
+            }
+            State::Op => {
+                let n = (byte & 0x3f) as usize + 1;
+                match byte & 0xc0 {
+                    KEEP => {
+                        self.skip(n, receiving);
+                        self.next_op()
+                    }
+                    _ => self.data(byte & 0xc0, n, 0)
+                }
+            }
+            State::Data { op, n, received } => {
+                self.channels[received % 3] = byte;
+                match op {
+                    SHIFT => {
+                        self.shift(byte as i8, receiving);
+                        self.data(op, n, received + 1)
+                    }
+                    _ if received % 3 < 2 => self.data(op, n, received + 1),
+                    RUN => {
+                        let [r, g, b] = self.channels;
+                        for _ in 0..n {
+                            self.put(Color { r, g, b }, receiving);
+                        }
+                        self.next_op()
+                    }
+                    _ => {
+                        let [r, g, b] = self.channels;
+                        self.put(Color { r, g, b }, receiving);
+                        self.data(op, n, received + 1)
+                    }
+                }
+            }
+            State::Crc(mut crc, n) => {
+                crc[n] = byte;
+                if n == 0 {
+                    State::Crc(crc, 1)
+                } else {
+                    return Some(self.end(u16::from_be_bytes(crc), receiving, frame));
+                }
+            }
+        };
+        None
+    }
+
+    /// Starts the frame of `header`, refuses it at once if no packet of its
+    /// count has that many bytes of ops.
+    fn begin_frame(&mut self, header: [u8; 6], receiving: &mut LiveFrame, frame: &LiveFrame) -> Result<State, Reply> {
+        let [flags, sequence, count_hi, count_lo, length_hi, length_lo] = header;
+        let count = u16::from_be_bytes([count_hi, count_lo]) as usize;
+        self.sequence = sequence;
+        self.left = u16::from_be_bytes([length_hi, length_lo]) as usize;
+        self.led = 0;
+        // At most a literal op of a single LED per LED
+        if count > MAX_LEDS || self.left > 4 * count {
+            return Err(Reply::Nak(sequence));
+        }
+        if flags & KEY != 0 {
+            receiving.clear(count);
+        } else {
+            self.ok = self.last == Some(sequence.wrapping_sub(1)) && frame.colors().len() == count;
+            *receiving = *frame;
+        }
+        Ok(self.next_op())
+    }
+
+    /// After the data of an op of `n` LEDs, `received` bytes of it are in.
+    fn data(&mut self, op: u8, n: usize, received: usize) -> State {
+        let len = if op == SHIFT { n } else if op == RUN { 3 } else { 3 * n };
+        if received == len {
+            self.next_op()
+        } else if self.left == 0 {
+            self.ok = false;
+            State::Crc([0; 2], 0)
+        } else {
+            State::Data { op, n, received }
+        }
+    }
+
+    fn next_op(&mut self) -> State {
+        if self.left > 0 { State::Op } else { State::Crc([0; 2], 0) }
+    }
+
+    fn skip(&mut self, n: usize, receiving: &LiveFrame) {
+        self.led += n;
+        self.ok &= self.led <= receiving.colors().len();
+    }
+
+    fn put(&mut self, color: Color, receiving: &mut LiveFrame) {
+        receiving.set(self.led, color);
+        self.skip(1, receiving);
+    }
+
+    fn shift(&mut self, delta: i8, receiving: &mut LiveFrame) {
+        let Some(&Color { r, g, b }) = receiving.colors().get(self.led) else {
+            self.ok = false;
+            return;
+        };
+        let shift = |channel: u8| (channel as i16 + delta as i16).clamp(0, 255) as u8;
+        self.put(Color { r: shift(r), g: shift(g), b: shift(b) }, receiving);
+    }
+
+    fn end(&mut self, crc: u16, receiving: &LiveFrame, frame: &mut LiveFrame) -> Reply {
+        self.state = State::Header([0; 6], 0);
+        if self.ok && crc == self.crc {
+            *frame = *receiving;
+            self.last = Some(self.sequence);
+            Reply::Ack(self.sequence)
+        } else {
+            Reply::Nak(self.sequence)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::{BLACK, RED, WHITE};
+
+    fn packet(flags: u8, sequence: u8, count: u16, ops: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![flags, sequence];
+        bytes.extend(count.to_be_bytes());
+        bytes.extend((ops.len() as u16).to_be_bytes());
+        bytes.extend(ops);
+        bytes.extend(crc16(0xffff, &bytes).to_be_bytes());
+        bytes
+    }
+
+    /// The replies to `packets`, without their start bytes, the frames taken
+    /// go to `frame`.
+    fn decode(decoder: &mut UploadDecoder, frame: &mut LiveFrame, packets: &[Vec<u8>]) -> Vec<Reply> {
+        let mut receiving = LiveFrame::new();
+        let mut replies = Vec::new();
+        for packet in packets {
+            decoder.begin();
+            replies.extend(packet.iter().filter_map(|&byte| decoder.decode(byte, &mut receiving, frame)));
+        }
+        replies
+    }
+
+    #[test]
+    fn crc_matches_the_check_value() {
+        assert_eq!(crc16(0xffff, b"123456789"), 0x29b1);
+    }
+
+    #[test]
+    fn ops_set_the_leds() {
+        let mut decoder = UploadDecoder::new();
+        let mut frame = LiveFrame::new();
+        let key = packet(KEY, 7, 6, &[RUN | 1, 255, 0, 0, LITERAL | 1, 1, 2, 3, 255, 255, 255]);
+        let delta = packet(0, 8, 6, &[KEEP | 1, SHIFT | 1, 0x80, 2, KEEP, RUN, 4, 5, 6]);
+        assert_eq!(decode(&mut decoder, &mut frame, &[key]), [Reply::Ack(7)]);
+        assert!(frame.colors() == [RED, RED, Color { r: 1, g: 2, b: 3 }, WHITE, BLACK, BLACK]);
+        assert_eq!(decode(&mut decoder, &mut frame, &[delta]), [Reply::Ack(8)]);
+        assert!(frame.colors() == [
+            RED, RED, BLACK, WHITE, BLACK, Color { r: 4, g: 5, b: 6 }
+        ]);
+    }
+
+    #[test]
+    fn broken_packets_are_refused() {
+        let mut decoder = UploadDecoder::new();
+        let mut frame = LiveFrame::new();
+        let mut corrupt = packet(KEY, 1, 2, &[RUN | 1, 255, 0, 0]);
+        corrupt[7] ^= 1;
+        let too_long = packet(KEY, 2, 2, &[RUN | 2, 255, 0, 0]);
+        let cut_off = packet(KEY, 3, 2, &[LITERAL | 1, 255, 0, 0]);
+        let too_many = packet(KEY, 4, MAX_LEDS as u16 + 1, &[]);
+        assert_eq!(
+            decode(&mut decoder, &mut frame, &[corrupt, too_long, cut_off, too_many]),
+            [Reply::Nak(1), Reply::Nak(2), Reply::Nak(3), Reply::Nak(4)]
+        );
+        assert!(frame.colors().is_empty());
+    }
+
+    #[test]
+    fn lengths_no_packet_needs_are_refused_at_once() {
+        let mut decoder = UploadDecoder::new();
+        let (mut receiving, mut frame) = (LiveFrame::new(), LiveFrame::new());
+        decoder.begin();
+        let replies: Vec<_> = [KEY, 9, 0, 2, 0, 9]
+            .iter()
+            .map(|&byte| decoder.decode(byte, &mut receiving, &mut frame))
+            .collect();
+        assert_eq!(replies, [None, None, None, None, None, Some(Reply::Nak(9))]);
+    }
+
+    #[test]
+    fn delta_frames_need_the_frame_before() {
+        let mut decoder = UploadDecoder::new();
+        let mut frame = LiveFrame::new();
+        let packets = [
+            packet(0, 0, 1, &[RUN, 255, 0, 0]),
+            packet(KEY, 5, 1, &[RUN, 255, 0, 0]),
+            packet(0, 7, 1, &[RUN, 0, 0, 0]),
+            packet(0, 6, 2, &[RUN, 0, 0, 0]),
+            packet(0, 6, 1, &[]),
+        ];
+        assert_eq!(
+            decode(&mut decoder, &mut frame, &packets),
+            [Reply::Nak(0), Reply::Ack(5), Reply::Nak(7), Reply::Nak(6), Reply::Ack(6)]
+        );
+        assert!(frame.colors() == [RED]);
+    }
+
+    #[test]
+    fn a_forgotten_frame_needs_a_key_frame() {
+        let mut decoder = UploadDecoder::new();
+        let mut frame = LiveFrame::new();
+        assert_eq!(decode(&mut decoder, &mut frame, &[packet(KEY, 1, 1, &[RUN, 255, 0, 0])]), [Reply::Ack(1)]);
+        decoder.forget();
+        assert_eq!(decode(&mut decoder, &mut frame, &[packet(0, 2, 1, &[])]), [Reply::Nak(2)]);
+    }
+}
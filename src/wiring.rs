@@ -0,0 +1,149 @@
+use crate::conf::MAX_STRIPS;
+use crate::layout::Layout;
+
+/// How the strips are chained physically
+///
+/// The shows address LED `y` of `strip` on the logical grid of the `Layout`.
+/// On the data line the strips follow each other in chain order, each strip
+/// either bottom to top or, if it is reversed, top to bottom.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Wiring {
+    strips: usize,
+    /// The logical strip at each position of the chain.
+    order: [u8; MAX_STRIPS],
+    /// Bit per logical strip, set if the strip starts at the top.
+    reversed: u32
+}
+
+impl Wiring {
+    /// The strips chained in order, all starting at the bottom.
+    pub const fn straight(strips: usize) -> Wiring {
+        assert!(strips <= MAX_STRIPS);
+        let mut order = [0u8; MAX_STRIPS];
+        let mut i = 0;
+        while i < MAX_STRIPS {
+            order[i] = i as u8;
+            i += 1;
+        }
+        Wiring { strips, order, reversed: 0 }
+    }
+
+    /// Zig-zag wiring: every second strip of the chain runs top to bottom.
+    pub const fn serpentine(strips: usize) -> Wiring {
+        let mut wiring = Wiring::straight(strips);
+        let mut i = 1;
+        while i < strips {
+            wiring = wiring.reverse(i);
+            i += 2;
+        }
+        wiring
+    }
+
+    /// The strips chained in the given order of logical strips.
+    pub const fn with_order(order: &[u8]) -> Wiring {
+        assert!(order.len() <= MAX_STRIPS);
+        let mut wiring = Wiring::straight(order.len());
+        let mut seen = 0u32;
+        let mut i = 0;
+        while i < order.len() {
+            let strip = order[i] as usize;
+            assert!(strip < order.len() && seen & (1 << strip) == 0, "order is not a permutation");
+            seen |= 1 << strip;
+            wiring.order[i] = order[i];
+            i += 1;
+        }
+        wiring
+    }
+
+    /// Flips the direction of a logical strip, e.g. one mounted upside down.
+    pub const fn reverse(mut self, strip: usize) -> Wiring {
+        assert!(strip < self.strips);
+        self.reversed ^= 1 << strip;
+        self
+    }
+
+    pub fn strips(&self) -> usize {
+        self.strips
+    }
+
+    pub fn is_reversed(&self, strip: usize) -> bool {
+        self.reversed & (1 << strip) != 0
+    }
+
+    /// The logical LED index of every physical LED, in chain order.  Dead
+    /// LEDs are skipped, they are bridged over in the data line.
+    pub fn physical_to_logical(&self, layout: Layout) -> impl Iterator<Item = usize> + '_ {
+        self.order[..self.strips].iter().flat_map(move |&strip| {
+            let strip = strip as usize;
+            let length = layout.strip_length(strip);
+            let reversed = self.is_reversed(strip);
+            (0..length)
+                .map(move |y| if reversed { length - 1 - y } else { y })
+                .filter(move |&y| !layout.is_dead(strip, y))
+                .map(move |y| layout.index(strip, y))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn map(wiring: Wiring, layout: Layout) -> Vec<usize> {
+        wiring.physical_to_logical(layout).collect()
+    }
+
+    #[test]
+    fn straight_wiring_is_the_identity() {
+        let layout = Layout::new(3, 4, true);
+        assert_eq!(map(Wiring::straight(3), layout), (0..12).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn serpentine_reverses_every_second_strip() {
+        let layout = Layout::new(3, 2, true);
+        assert_eq!(map(Wiring::serpentine(3), layout), [0, 1, 3, 2, 4, 5]);
+    }
+
+    #[test]
+    fn single_strip_upside_down() {
+        let layout = Layout::new(2, 3, true);
+        assert_eq!(map(Wiring::straight(2).reverse(0), layout), [2, 1, 0, 3, 4, 5]);
+    }
+
+    #[test]
+    fn custom_order() {
+        let layout = Layout::new(3, 2, false);
+        assert_eq!(map(Wiring::with_order(&[2, 0, 1]).reverse(0), layout), [4, 5, 1, 0, 2, 3]);
+    }
+
+    #[test]
+    fn short_strips_and_dead_leds_are_skipped() {
+        let layout = Layout::new(2, 3, true).with_strip_length(0, 2).with_dead_led(1, 1);
+        assert_eq!(map(Wiring::straight(2), layout), [0, 1, 3, 5]);
+        assert_eq!(map(Wiring::serpentine(2), layout), [0, 1, 5, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "permutation")]
+    fn order_must_be_a_permutation() {
+        Wiring::with_order(&[0, 2, 2]);
+    }
+
+    proptest! {
+        #[test]
+        fn every_led_is_sent_once(strips in 1..=MAX_STRIPS, length in 1usize..60, reversed: u32, rotate in 0..MAX_STRIPS) {
+            let layout = Layout::new(strips, length, true);
+            let order: Vec<u8> = (0..strips).map(|s| ((s + rotate) % strips) as u8).collect();
+            let mut wiring = Wiring::with_order(&order);
+            for strip in (0..strips).filter(|s| reversed & (1 << s) != 0) {
+                wiring = wiring.reverse(strip);
+            }
+
+            let mut leds = map(wiring, layout);
+            leds.sort();
+            prop_assert_eq!(leds, (0..layout.num_leds()).collect::<Vec<_>>());
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Encoding of the LED colours for WS2812 and SK6812 strips
+//!
+//! These single wire LEDs take 8 bits per channel, green, red, blue for the
+//! WS2812 and green, red, blue, white for the RGBW SK6812.  There is no global
+//! brightness, the master brightness is applied to the channels.
+//!
+//! The PIO program of the firmware sends one word per LED, most significant
+//! bit first.  Every bit takes `CYCLES_PER_BIT` cycles of the state machine:
+//! high for `T1` cycles, then high for a one or low for a zero for `T2` cycles,
+//! then low for `T3` cycles.  The line stays low between the frames, which
+//! latches the data.
+
+use crate::protocol::ChannelOrder;
+
+/// The data rate of the LEDs.
+pub const BIT_RATE: u32 = 800_000;
+pub const T1: u8 = 2;
+pub const T2: u8 = 5;
+pub const T3: u8 = 3;
+pub const CYCLES_PER_BIT: u32 = (T1 + T2 + T3) as u32;
+
+/// The number of cycles the line is high for a bit.
+pub const fn high_cycles(bit: bool) -> u32 {
+    if bit { (T1 + T2) as u32 } else { T1 as u32 }
+}
+
+/// The clock divisor of the state machine as 16.8 fixed point.
+pub fn clock_divisor(system_hz: u32) -> (u16, u8) {
+    let divisor = (system_hz as u64 * 256 / (BIT_RATE as u64 * CYCLES_PER_BIT as u64)) as u32;
+    ((divisor >> 8) as u16, divisor as u8)
+}
+
+/// The line has to stay low that long to latch the data.
+pub const RESET_US: u32 = 300;
+
+/// The bits per LED.
+pub const fn bits(rgbw: bool) -> u8 {
+    if rgbw { 32 } else { 24 }
+}
+
+/// Encodes 16 bit channel intensities (red, green, blue) dimmed by `master`
+/// into the word for a LED, aligned to the most significant bit.  `order` is
+/// the order of the colour channels, white comes last.
+pub fn encode(rgb: [u16; 3], master: u8, order: ChannelOrder, rgbw: bool) -> u32 {
+    let mut channels = rgb.map(|c| ((c as u32 * master as u32 / 255) >> 8) as u8);
+    let mut white = 0;
+    if rgbw {
+        white = channels.into_iter().min().unwrap_or(0);
+        channels = channels.map(|c| c - white);
+    }
+
+    let mut bytes = [0, 0, 0, white];
+    for (channel, offset) in channels.into_iter().zip(order.offsets()) {
+        bytes[offset - 1] = channel;
+    }
+    u32::from_be_bytes(bytes)
+}
+
+/// The words for the FIFO of the state machine from the data of a chain.
+pub fn words(bytes: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    bytes.chunks_exact(4).map(|word| u32::from_be_bytes([word[0], word[1], word[2], word[3]]))
+}
+
+/// The time to shift out the data of `leds` LEDs and latch it.
+pub const fn frame_us(leds: usize, rgbw: bool) -> u32 {
+    (leds as u32 * bits(rgbw) as u32 * CYCLES_PER_BIT).div_ceil(CYCLES_PER_BIT * BIT_RATE / 1_000_000)
+        + RESET_US
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apa102::widen;
+    use proptest::prelude::*;
+
+    const NS_PER_CYCLE: u32 = 1_000_000_000 / (BIT_RATE * CYCLES_PER_BIT);
+
+    #[test]
+    fn bit_timing_meets_the_datasheet() {
+        // T0H 0.4 µs, T1H 0.8 µs, each +-150 ns, 1.25 µs per bit
+        assert!((250..=550).contains(&(high_cycles(false) * NS_PER_CYCLE)));
+        assert!((650..=950).contains(&(high_cycles(true) * NS_PER_CYCLE)));
+        assert_eq!(CYCLES_PER_BIT * NS_PER_CYCLE, 1250);
+    }
+
+    #[test]
+    fn clock_divisor_of_the_pico() {
+        // 125 MHz / 8 MHz = 15.625
+        assert_eq!(clock_divisor(125_000_000), (15, 160));
+    }
+
+    #[test]
+    fn grb_words() {
+        let rgb = [widen(1), widen(2), widen(3)];
+        assert_eq!(encode(rgb, 255, ChannelOrder::Grb, false), 0x02_01_03_00);
+        assert_eq!(encode(rgb, 255, ChannelOrder::Rgb, false), 0x01_02_03_00);
+    }
+
+    #[test]
+    fn rgbw_moves_the_common_part_to_white() {
+        let rgb = [widen(200), widen(50), widen(80)];
+        assert_eq!(encode(rgb, 255, ChannelOrder::Grb, true), 0x0096_1e32);
+    }
+
+    #[test]
+    fn master_brightness_dims_the_channels() {
+        assert_eq!(encode([widen(200), 0, 0], 128, ChannelOrder::Rgb, false), 0x64_00_00_00);
+        assert_eq!(encode([widen(255); 3], 0, ChannelOrder::Rgb, true), 0);
+    }
+
+    #[test]
+    fn words_of_a_chain() {
+        let bytes = [2, 1, 3, 0, 0, 255, 0, 0];
+        assert_eq!(words(&bytes).collect::<Vec<_>>(), [0x02_01_03_00, 0x00_ff_00_00]);
+    }
+
+    #[test]
+    fn frame_time_of_half_the_flower() {
+        // 720 LEDs of 24 bits at 1.25 µs
+        assert_eq!(frame_us(720, false), 21_600 + RESET_US);
+        assert_eq!(frame_us(1, true), 40 + RESET_US);
+    }
+
+    proptest! {
+        #[test]
+        fn rgbw_keeps_the_colour(r: u8, g: u8, b: u8) {
+            let [gr, rd, bl, w] = encode([widen(r), widen(g), widen(b)], 255, ChannelOrder::Grb, true).to_be_bytes();
+            prop_assert_eq!([rd + w, gr + w, bl + w], [r, g, b]);
+            prop_assert!(rd == 0 || gr == 0 || bl == 0);
+        }
+    }
+}